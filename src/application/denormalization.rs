@@ -0,0 +1,30 @@
+use rocket::response::status::Custom;
+
+use crate::domain::post::Post;
+use crate::domain::post_repository::PostRepository;
+
+/// Fills in the copies of related documents a post carries, so it can be matched and
+/// displayed without joins at search time. Only `tenant`'s documents are copied.
+pub async fn denormalize<R: PostRepository>(post_repository: &R, post: &mut Post, tenant: &str) -> Result<(), Custom<sea_orm::prelude::Json>> {
+	if let Post::Course(course) = post {
+		let mut ids = course.category_ids.clone();
+		ids.extend(course.instructor_id);
+
+		let related = post_repository.get_posts(&ids, tenant).await?;
+		course.categories = related
+			.iter()
+			.filter_map(|p| match p {
+				Post::Category(category) if course.category_ids.contains(&category.id) => Some(category.into()),
+				_ => None
+			})
+			.collect();
+		course.instructor = related
+			.iter()
+			.find_map(|p| match p {
+				Post::User(user) if Some(user.id) == course.instructor_id => Some(user.into()),
+				_ => None
+			});
+	}
+
+	Ok(())
+}
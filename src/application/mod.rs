@@ -1,2 +1,3 @@
 // This file is intentionally left blank.
+pub mod denormalization;
 pub mod use_cases;
\ No newline at end of file
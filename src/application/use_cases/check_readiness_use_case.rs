@@ -0,0 +1,37 @@
+use std::sync::Arc;
+use rocket::http::Status;
+use rocket::response::status::Custom;
+use rocket::serde::json::json;
+use serde_json::Map;
+
+use crate::domain::health::{HealthCheck, HealthStatus};
+use crate::domain::post_repository::{PostRepository, PostRepositoryImpl};
+
+pub struct CheckReadinessUseCase {
+	post_repository: Arc<PostRepositoryImpl>,
+}
+
+impl CheckReadinessUseCase {
+	pub fn new(post_repository: Arc<PostRepositoryImpl>) -> Self {
+		CheckReadinessUseCase { post_repository }
+	}
+
+	/// Ready when every check is up; otherwise 503, so orchestration holds traffic back.
+	pub async fn execute(&self) -> Custom<sea_orm::prelude::Json> {
+		let mut checks: Vec<HealthCheck> = vec![self.post_repository.client_health().await];
+		checks.extend(self.post_repository.cluster_health().await);
+
+		let up = checks.iter().all(|check| check.status == HealthStatus::Up);
+		let report: Map<String, sea_orm::prelude::Json> = checks
+			.iter()
+			.map(|check| (check.name.to_string(), json!(check)))
+			.collect();
+		Custom(
+			if up { Status::Ok } else { Status::ServiceUnavailable },
+			json!({
+				"status": if up { HealthStatus::Up } else { HealthStatus::Down },
+				"checks": report
+			})
+		)
+	}
+}
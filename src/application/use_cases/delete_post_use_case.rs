@@ -6,26 +6,74 @@ use uuid::Uuid;
 
 use crate::domain::post_repository::{PostRepository, PostRepositoryImpl};
 
-pub struct DeletePostUseCase<'a> {
-	post_repository: Arc<PostRepositoryImpl<'a>>,
+pub struct DeletePostUseCase<R = PostRepositoryImpl> {
+	post_repository: Arc<R>,
 }
 
-impl <'a> DeletePostUseCase <'a> {
-	pub fn new(post_repository: Arc<PostRepositoryImpl<'a>>) -> Self {
+impl<R: PostRepository> DeletePostUseCase<R> {
+	pub fn new(post_repository: Arc<R>) -> Self {
 		DeletePostUseCase { post_repository }
 	}
 
-	pub async fn execute(&self, post_id: &Uuid) -> Result<Custom<sea_orm::prelude::Json>, Custom<sea_orm::prelude::Json>> {
-		if !self.post_repository.check_if_exists(post_id).await {
+	pub async fn execute(&self, post_id: &Uuid, tenant: &str) -> Result<Custom<sea_orm::prelude::Json>, Custom<sea_orm::prelude::Json>> {
+		self.post_repository.check_writable().await?;
+		if !self.post_repository.check_if_exists(post_id, tenant).await? {
 			return Err(Custom(Status::NotFound, json!({
 				"status": Status::NotFound,
 				"message": "Post not found"
 			})))
 		}
 
-		match self.post_repository.delete(post_id).await {
-			Ok(response) => return Ok(response),
-			Err(e) => return Err(e)
-		};
+		self.post_repository.delete(post_id, tenant).await
 	}
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::domain::post::Post;
+	use crate::infrastructure::repositories::in_memory_post_repository::InMemoryPostRepository;
+
+	fn category(id: Uuid) -> Post {
+		serde_json::from_value(json!({"type": "Category", "id": id, "name": "Rust"})).unwrap()
+	}
+
+	#[tokio::test]
+	async fn posts_of_other_tenants_are_not_found_and_kept() {
+		let repository = Arc::new(InMemoryPostRepository::default());
+		let id = Uuid::new_v4();
+		repository.insert(&category(id), "acme");
+		let use_case = DeletePostUseCase::new(repository.clone());
+
+		let result = use_case.execute(&id, "globex").await;
+
+		assert!(matches!(result, Err(Custom(status, _)) if status == Status::NotFound));
+		assert!(repository.document(&id).is_some());
+	}
+
+	#[tokio::test]
+	async fn posts_of_the_callers_tenant_are_deleted() {
+		let repository = Arc::new(InMemoryPostRepository::default());
+		let id = Uuid::new_v4();
+		repository.insert(&category(id), "acme");
+		let use_case = DeletePostUseCase::new(repository.clone());
+
+		use_case.execute(&id, "acme").await.unwrap();
+
+		assert!(repository.document(&id).is_none());
+	}
+
+	#[tokio::test]
+	async fn deletes_during_a_migration_are_refused() {
+		let repository = Arc::new(InMemoryPostRepository::default());
+		let id = Uuid::new_v4();
+		repository.insert(&category(id), "acme");
+		repository.migrating.store(true, std::sync::atomic::Ordering::SeqCst);
+		let use_case = DeletePostUseCase::new(repository.clone());
+
+		let result = use_case.execute(&id, "acme").await;
+
+		assert!(matches!(result, Err(Custom(status, _)) if status == Status::ServiceUnavailable));
+		assert!(repository.document(&id).is_some());
+	}
+}
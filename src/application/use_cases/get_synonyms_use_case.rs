@@ -0,0 +1,20 @@
+use std::sync::Arc;
+use rocket::response::status::Custom;
+
+use crate::domain::post_repository::{PostRepository, PostRepositoryImpl};
+use crate::interfaces::dtos::SynonymsDto;
+
+pub struct GetSynonymsUseCase {
+	post_repository: Arc<PostRepositoryImpl>,
+}
+
+impl GetSynonymsUseCase {
+	pub fn new(post_repository: Arc<PostRepositoryImpl>) -> Self {
+		GetSynonymsUseCase { post_repository }
+	}
+
+	pub async fn execute(&self) -> Result<SynonymsDto, Custom<sea_orm::prelude::Json>> {
+		let synonyms = self.post_repository.get_synonyms().await?;
+		Ok(SynonymsDto { synonyms })
+	}
+}
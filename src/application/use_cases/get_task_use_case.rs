@@ -0,0 +1,38 @@
+use std::sync::Arc;
+use rocket::http::Status;
+use rocket::response::status::Custom;
+use rocket::serde::json::json;
+
+use crate::domain::access::Caller;
+use crate::domain::post_repository::{PostRepository, PostRepositoryImpl};
+
+pub struct GetTaskUseCase {
+	post_repository: Arc<PostRepositoryImpl>,
+}
+
+impl GetTaskUseCase {
+	pub fn new(post_repository: Arc<PostRepositoryImpl>) -> Self {
+		GetTaskUseCase { post_repository }
+	}
+
+	/// Service callers follow any task; everyone else only their tenant's.
+	pub async fn execute(&self, task_id: &str, caller: &Caller) -> Result<Custom<sea_orm::prelude::Json>, Custom<sea_orm::prelude::Json>> {
+		// Task ids look like `<node id>:<sequence number>`
+		let valid = match task_id.split_once(':') {
+			Some((node, number)) => !node.is_empty()
+				&& node.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+				&& !number.is_empty()
+				&& number.chars().all(|c| c.is_ascii_digit()),
+			None => false
+		};
+		if !valid {
+			return Err(Custom(Status::BadRequest, json!({
+				"success": false,
+				"message": format!("Invalid task id: {}", task_id)
+			})))
+		}
+
+		let tenant = (!caller.service).then(|| caller.tenant());
+		self.post_repository.get_task(task_id, tenant).await
+	}
+}
@@ -3,28 +3,29 @@ use rocket::http::Status;
 use rocket::response::status::Custom;
 use rocket::serde::json::{Json, json};
 
+use crate::application::denormalization::denormalize;
 use crate::domain::post::Post;
 use crate::domain::post_repository::{PostRepository, PostRepositoryImpl};
 
-pub struct IndexPostUseCase<'a> {
-	post_repository: Arc<PostRepositoryImpl<'a>>,
+pub struct IndexPostUseCase {
+	post_repository: Arc<PostRepositoryImpl>,
 }
 
-impl <'a> IndexPostUseCase <'a> {
-	pub fn new(post_repository: Arc<PostRepositoryImpl<'a>>) -> Self {
+impl IndexPostUseCase {
+	pub fn new(post_repository: Arc<PostRepositoryImpl>) -> Self {
 		IndexPostUseCase { post_repository }
 	}
 
-	pub async fn execute(&self, post: Json<Post>) -> Result<Custom<sea_orm::prelude::Json>, Custom<sea_orm::prelude::Json>> {
-		if let Err(err) = self.post_repository.create_index_if_not_exists(false, &post).await {
-			return Err(err);
-		};
+	pub async fn execute(&self, post: Json<Post>, tenant: &str) -> Result<Custom<sea_orm::prelude::Json>, Custom<sea_orm::prelude::Json>> {
+		self.post_repository.create_index_if_not_exists(false).await?;
+		self.post_repository.check_writable().await?;
 
-		if let Err(err)  = self.post_repository.set_refresh_interval(json!("-1")).await {
-			return Err(err)
-		};
+		let mut post = post.into_inner();
+		denormalize(self.post_repository.as_ref(), &mut post, tenant).await?;
+
+		self.post_repository.set_refresh_interval(json!("-1")).await?;
 		
-		match self.post_repository.index(&[post.into_inner()]).await {
+		match self.post_repository.index(&[post], tenant).await {
 			Ok(response) => {
 				if response.contains("Error") {
 					return Err(Custom(Status::InternalServerError, json!({
@@ -36,9 +37,7 @@ impl <'a> IndexPostUseCase <'a> {
 			Err(e) => return Err(e)
 		};
 
-		if let Err(err)  = self.post_repository.set_refresh_interval(json!(null)).await {
-			return Err(err)
-		};
+		self.post_repository.set_refresh_interval(json!(null)).await?;
 
 		Ok(Custom(Status::Created, json!({
 			"success": true,
@@ -1,4 +1,9 @@
 pub mod search_post_use_case;
 pub mod index_post_use_case;
 pub mod update_post_use_case;
-pub mod delete_post_use_case;
\ No newline at end of file
+pub mod delete_post_use_case;
+pub mod get_synonyms_use_case;
+pub mod update_synonyms_use_case;
+pub mod get_task_use_case;
+pub mod check_readiness_use_case;
+pub mod reindex_use_case;
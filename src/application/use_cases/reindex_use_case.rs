@@ -0,0 +1,88 @@
+use std::sync::Arc;
+use std::time::Duration;
+use rocket::http::Status;
+use rocket::response::status::Custom;
+use rocket::serde::json::json;
+
+use crate::domain::post_repository::{Migration, PostRepository, PostRepositoryImpl};
+
+/// How often the copy's progress is checked.
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Mappings and analyzers only apply to indices created after they changed. Reindexing copies
+/// the posts into a new index that has them, then switches the index name to it.
+pub struct ReindexUseCase {
+	post_repository: Arc<PostRepositoryImpl>,
+}
+
+impl ReindexUseCase {
+	pub fn new(post_repository: Arc<PostRepositoryImpl>) -> Self {
+		ReindexUseCase { post_repository }
+	}
+
+	/// Answers once the copy started; the switch follows in the background when it completes.
+	/// Until then, posts cannot be written: writes answer 503 on every instance.
+	pub async fn execute(&self) -> Result<Custom<sea_orm::prelude::Json>, Custom<sea_orm::prelude::Json>> {
+		let migration = self.post_repository.start_reindex().await?;
+		println!("Copying posts from {} to {} in task {}", migration.source, migration.target, migration.task);
+
+		let response = json!({
+			"success": true,
+			"task": migration.task,
+			"source": migration.source,
+			"target": migration.target
+		});
+		let post_repository = self.post_repository.clone();
+		tokio::spawn(async move {
+			if !complete(&post_repository, &migration).await {
+				abort(&post_repository, &migration).await;
+			}
+		});
+
+		Ok(Custom(Status::Accepted, response))
+	}
+}
+
+/// Waits for the copy, then switches to the new index unless some posts failed to copy.
+/// Returns whether it switched.
+async fn complete(post_repository: &PostRepositoryImpl, migration: &Migration) -> bool {
+	let status = loop {
+		tokio::time::sleep(POLL_INTERVAL).await;
+		match post_repository.get_task(&migration.task, None).await {
+			Ok(Custom(_, status)) if status["completed"] == true => break status,
+			Ok(_) => {}
+			Err(Custom(status, _)) if status == Status::NotFound => {
+				println!("Reindex task {} disappeared, {} is left as is", migration.task, migration.source);
+				return false;
+			}
+			Err(e) => println!("Failed to check reindex task {}: {}", migration.task, e.1["message"]),
+		}
+	};
+
+	if status["success"] != true {
+		println!(
+			"Copying posts to {} failed, {} is left as is: {} {}",
+			migration.target, migration.source, status["error"], status["failures"]
+		);
+		return false;
+	}
+	match post_repository.switch_index(migration).await {
+		Ok(()) => {
+			println!("Switched to {}, copied from {}", migration.target, migration.source);
+			true
+		}
+		Err(e) => {
+			println!("Failed to switch to {}: {}", migration.target, e.1["message"]);
+			false
+		}
+	}
+}
+
+/// Lets the old index take writes again. The new one is left for inspection and must be
+/// deleted before retrying.
+async fn abort(post_repository: &PostRepositoryImpl, migration: &Migration) {
+	match post_repository.abort_reindex(migration).await {
+		Ok(()) => println!("{} takes writes again", migration.source),
+		Err(e) => println!("Failed to let {} take writes again: {}", migration.source, e.1["message"]),
+	}
+}
@@ -1,52 +1,317 @@
 use std::sync::Arc;
+use rocket::http::Status;
 use rocket::response::status::Custom;
 use rocket::serde::json::json;
+use uuid::Uuid;
 
+use crate::domain::post::{Post, PostType};
 use crate::domain::post_repository::{PostRepository, PostRepositoryImpl};
+use crate::domain::search::{Facet, SortOption};
+use crate::infrastructure::config::SearchSettings;
+use crate::interfaces::dtos::{FacetDto, HighlightOptionsDto, SearchFiltersDto, SearchRequestDto, SearchResponseDto};
 
-pub struct SearchPostUseCase<'a> {
-	post_repository: Arc<PostRepositoryImpl<'a>>,
+pub struct SearchPostUseCase<R = PostRepositoryImpl> {
+	post_repository: Arc<R>,
+	settings: SearchSettings,
 }
 
-impl <'a> SearchPostUseCase <'a> {
-	pub fn new(post_repository: Arc<PostRepositoryImpl<'a>>) -> Self {
-		SearchPostUseCase { post_repository }
+impl<R: PostRepository> SearchPostUseCase<R> {
+	pub fn new(post_repository: Arc<R>, settings: SearchSettings) -> Self {
+		SearchPostUseCase { post_repository, settings }
 	}
 
-	pub async fn execute(&self, q: &str) -> Result<Custom<sea_orm::prelude::Json>, Custom<sea_orm::prelude::Json>> {
-		let query = if q.is_empty() {
+	/// Without `facets` the response is the plain list of hits; with them it is a
+	/// `SearchResponseDto` carrying the hits and one entry per requested facet. With
+	/// `explain`, which only administrators may ask for, every hit carries Elasticsearch's
+	/// breakdown of its score.
+	pub async fn execute(&self, request: &SearchRequestDto<'_>) -> Result<Custom<sea_orm::prelude::Json>, Custom<sea_orm::prelude::Json>> {
+		let SearchRequestDto { caller, q, filters, facets, sort, projection, highlight, explain, page, per_page } = request;
+		let sort = *sort;
+		let allowed_sorts = SortOption::allowed_for(filters.post_type);
+		if !allowed_sorts.contains(&sort) {
+			let allowed: Vec<&str> = allowed_sorts.iter().map(SortOption::name).collect();
+			return Err(Custom(Status::BadRequest, json!({
+				"success": false,
+				"message": format!("Cannot sort by {} here, expected one of: {}", sort.name(), allowed.join(", "))
+			})));
+		}
+
+		if *explain && !caller.admin {
+			return Err(Custom(Status::Forbidden, json!({
+				"success": false,
+				"message": "Only administrators can explain scores"
+			})));
+		}
+
+		let mut query = if q.is_empty() {
 			json!({
 				"query": {
 					"match_all": {}
 				}
 			})
 		} else {
+			let fields = PostType::text_fields_of(filters.post_type);
 			json!({
-				"query": {
-					"multi_match": {
-						"query": q,
-						"fields": ["name", "lastname", "nickname", "description"],
-						"operator": "and",
-						"type": "cross_fields",
-					}
-				},
-				"highlight": {
-					"fields": {
-						"name": {},
-						"lastname": {},
-						"nickname": {},
-						"description": {}
-					},
-					"pre_tags": ["<b>"],
-					"post_tags": ["</b>"]
-				}
+				"query": Post::text_query(q, &fields),
+				"highlight": Self::highlight(highlight, &fields)?
 			})
 		};
-        println!("Cluster address: {:?}", std::env::var("ELASTICSEARCH_URL").unwrap());
 
-		match self.post_repository.search(query).await {
-			Ok(response) => return Ok(response),
-			Err(e) => return Err(e)
+		if sort == SortOption::Relevance {
+			let created_at_mapped = self.post_repository.maps_field("created_at").await?;
+			query["query"] = self.settings.ranking.function_score(query["query"].take(), created_at_mapped);
+		}
+		let (from, size) = self.page(*page, *per_page)?;
+		query["from"] = json!(from);
+		query["size"] = json!(size);
+		query["sort"] = sort.clause();
+		if *explain {
+			query["explain"] = json!(true);
+		}
+
+		let mut active_filters = Vec::new();
+		let mut query_filters = Vec::new();
+		for (facet, clause) in Self::filter_clauses(filters)? {
+			match facet {
+				Some(facet) if facets.contains(&facet) => active_filters.push((facet, clause)),
+				_ => query_filters.push(clause)
+			}
+		}
+		query_filters.extend(caller.visibility_filter());
+		if !query_filters.is_empty() {
+			query["query"] = json!({
+				"bool": {
+					"must": query["query"].take(),
+					"filter": query_filters
+				}
+			});
+		}
+
+		Self::add_facets(&mut query, facets, &active_filters);
+
+		let result = self.post_repository.search(query, projection, caller.tenant()).await?;
+		if facets.is_empty() {
+			return Ok(Custom(Status::Ok, json!(result.posts)));
+		}
+
+		let facets = facets
+			.iter()
+			.map(|facet| (
+				facet.name().to_string(),
+				FacetDto::from_aggregation(facet, &result.aggregations[facet.name()])
+			))
+			.collect();
+		Ok(Custom(Status::Ok, json!(SearchResponseDto { hits: result.posts, facets })))
+	}
+
+	/// Offset and size of the requested page, 1-based, within the configured limits.
+	fn page(&self, page: Option<u32>, per_page: Option<u32>) -> Result<(u32, u32), Custom<sea_orm::prelude::Json>> {
+		let page = page.unwrap_or(1);
+		let per_page = per_page.unwrap_or(self.settings.default_page_size);
+		if page == 0 || per_page == 0 || per_page > self.settings.max_page_size {
+			return Err(Custom(Status::BadRequest, json!({
+				"success": false,
+				"message": format!("page must be at least 1 and per_page between 1 and {}", self.settings.max_page_size)
+			})));
+		}
+
+		let from = (page - 1).saturating_mul(per_page);
+		if from.saturating_add(per_page) > self.settings.max_result_window {
+			return Err(Custom(Status::BadRequest, json!({
+				"success": false,
+				"message": format!("Cannot page past the first {} results", self.settings.max_result_window)
+			})));
+		}
+		Ok((from, per_page))
+	}
+
+	fn highlight(options: &HighlightOptionsDto, fields: &[&str]) -> Result<sea_orm::prelude::Json, Custom<sea_orm::prelude::Json>> {
+		let encoder = options.encoder.unwrap_or_default();
+		let pre_tag = options.pre_tag.as_deref().unwrap_or("<b>");
+		let post_tag = options.post_tag.as_deref().unwrap_or("</b>");
+		if !encoder.allows_tags(pre_tag, post_tag) {
+			return Err(Custom(Status::BadRequest, json!({
+				"success": false,
+				"message": "Unsupported highlight tags, use <b>, <em>, <mark>, <strong> or empty tags"
+			})));
+		}
+
+		let mut highlight = json!({
+			"fields": {},
+			"pre_tags": [pre_tag],
+			"post_tags": [post_tag],
+			"encoder": encoder.encoder()
+		});
+		for field in fields {
+			highlight["fields"][*field] = json!({});
+		}
+		if let Some(fragment_size) = options.fragment_size {
+			highlight["fragment_size"] = json!(fragment_size);
+		}
+		if let Some(number_of_fragments) = options.number_of_fragments {
+			highlight["number_of_fragments"] = json!(number_of_fragments);
+		}
+		if let Some(require_field_match) = options.require_field_match {
+			highlight["require_field_match"] = json!(require_field_match);
+		}
+		Ok(highlight)
+	}
+
+	/// Translates the structured filters into filter clauses, each tagged with the facet
+	/// whose field it narrows. They run in filter context: no scoring, and cacheable.
+	fn filter_clauses(filters: &SearchFiltersDto) -> Result<Vec<(Option<Facet>, sea_orm::prelude::Json)>, Custom<sea_orm::prelude::Json>> {
+		let parse_id = |id: &String| Uuid::parse_str(id).map_err(|_| Custom(Status::BadRequest, json!({
+			"success": false,
+			"message": format!("Invalid id: {}", id)
+		})));
+		let mut clauses = Vec::new();
+
+		if let Some(post_type) = filters.post_type {
+			clauses.push((None, json!({"term": {"type": post_type.tag()}})));
+		}
+		if !filters.category.is_empty() {
+			let ids = filters.category.iter().map(parse_id).collect::<Result<Vec<Uuid>, _>>()?;
+			clauses.push((Some(Facet::Category), json!({"terms": {"category_ids": ids}})));
+		}
+		if let Some(instructor) = &filters.instructor {
+			clauses.push((None, json!({"term": {"instructor_id": parse_id(instructor)?}})));
+		}
+		if !filters.level.is_empty() {
+			clauses.push((Some(Facet::Level), json!({"terms": {"level": filters.level}})));
+		}
+		if !filters.language.is_empty() {
+			clauses.push((Some(Facet::Language), json!({"terms": {"language": filters.language}})));
+		}
+		if filters.price_min.is_some() || filters.price_max.is_some() {
+			if let (Some(min), Some(max)) = (filters.price_min, filters.price_max) {
+				if min > max {
+					return Err(Custom(Status::BadRequest, json!({
+						"success": false,
+						"message": "price_min must not be greater than price_max"
+					})));
+				}
+			}
+			clauses.push((Some(Facet::Price), json!({"range": {"price": {"gte": filters.price_min, "lte": filters.price_max}}})));
+		}
+		if let Some(rating_min) = filters.rating_min {
+			clauses.push((None, json!({"range": {"rating": {"gte": rating_min}}})));
+		}
+		if let Some(published) = filters.published {
+			clauses.push((None, json!({"term": {"published": published}})));
+		}
+
+		Ok(clauses)
+	}
+
+	/// Adds the facet aggregations. Filters on faceted fields go to `post_filter` so they
+	/// narrow the hits without collapsing the buckets of their own facet.
+	fn add_facets(query: &mut sea_orm::prelude::Json, facets: &[Facet], active_filters: &[(Facet, sea_orm::prelude::Json)]) {
+		if !active_filters.is_empty() {
+			let filters: Vec<&sea_orm::prelude::Json> = active_filters.iter().map(|(_, filter)| filter).collect();
+			query["post_filter"] = json!({
+				"bool": {
+					"filter": filters
+				}
+			});
+		}
+		for facet in facets {
+			query["aggs"][facet.name()] = facet.aggregation(active_filters);
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::domain::access::Caller;
+	use crate::domain::search::Projection;
+	use crate::infrastructure::repositories::in_memory_post_repository::InMemoryPostRepository;
+
+	fn category(name: &str) -> Post {
+		serde_json::from_value(json!({"type": "Category", "id": Uuid::new_v4(), "name": name})).unwrap()
+	}
+
+	fn request(caller: Caller) -> SearchRequestDto<'static> {
+		SearchRequestDto {
+			caller,
+			q: "",
+			filters: SearchFiltersDto::default(),
+			facets: Vec::new(),
+			sort: SortOption::default(),
+			projection: Projection::default(),
+			highlight: HighlightOptionsDto::default(),
+			explain: false,
+			page: None,
+			per_page: None,
+		}
+	}
+
+	fn hit_ids(response: &sea_orm::prelude::Json) -> Vec<String> {
+		response.as_array().unwrap().iter().map(|hit| hit["post"]["id"].as_str().unwrap().to_string()).collect()
+	}
+
+	#[tokio::test]
+	async fn searches_only_return_the_callers_tenant() {
+		let repository = Arc::new(InMemoryPostRepository::default());
+		let acme = category("Acme");
+		let globex = category("Globex");
+		repository.insert(&acme, "acme");
+		repository.insert(&globex, "globex");
+		let use_case = SearchPostUseCase::new(repository, SearchSettings::default());
+
+		let caller = Caller { tenant_id: Some("acme".into()), ..Caller::default() };
+		let response = use_case.execute(&request(caller)).await.unwrap();
+
+		assert_eq!(hit_ids(&response.1), vec![acme.id().to_string()]);
+	}
+
+	#[tokio::test]
+	async fn anonymous_searches_only_return_the_default_tenant() {
+		let repository = Arc::new(InMemoryPostRepository::default());
+		let legacy = Uuid::new_v4();
+		// Posts indexed before tenants existed carry no tenant.
+		repository.documents.lock().unwrap().insert(legacy, json!({"type": "Category", "id": legacy, "name": "Legacy"}));
+		repository.insert(&category("Acme"), "acme");
+		let use_case = SearchPostUseCase::new(repository, SearchSettings::default());
+
+		let response = use_case.execute(&request(Caller::anonymous())).await.unwrap();
+
+		assert_eq!(hit_ids(&response.1), vec![legacy.to_string()]);
+	}
+
+	#[tokio::test]
+	async fn recency_only_ranks_indices_that_map_created_at() {
+		let repository = Arc::new(InMemoryPostRepository::default());
+		let use_case = SearchPostUseCase::new(repository.clone(), SearchSettings::default());
+
+		use_case.execute(&request(Caller::anonymous())).await.unwrap();
+		repository.unmapped_fields.lock().unwrap().push("created_at".into());
+		use_case.execute(&request(Caller::anonymous())).await.unwrap();
+
+		let recency: Vec<bool> = repository
+			.searches
+			.lock()
+			.unwrap()
+			.iter()
+			.map(|search| search.to_string().contains("gauss"))
+			.collect();
+		assert_eq!(recency, [true, false]);
+	}
+
+	#[tokio::test]
+	async fn only_administrators_can_explain_scores() {
+		let repository = Arc::new(InMemoryPostRepository::default());
+		let use_case = SearchPostUseCase::new(repository.clone(), SearchSettings::default());
+
+		let anonymous = SearchRequestDto { explain: true, ..request(Caller::anonymous()) };
+		let Err(Custom(status, _)) = use_case.execute(&anonymous).await else {
+			panic!("explaining should be refused to anonymous callers");
 		};
+		assert_eq!(status, Status::Forbidden);
+		assert!(repository.searches.lock().unwrap().is_empty());
+
+		let admin = SearchRequestDto { explain: true, ..request(Caller { admin: true, ..Caller::default() }) };
+		use_case.execute(&admin).await.unwrap();
+		assert_eq!(repository.searches.lock().unwrap()[0]["explain"], true);
 	}
-}
\ No newline at end of file
+}
@@ -4,29 +4,122 @@ use rocket::response::status::Custom;
 use rocket::serde::json::{Json, json};
 use uuid::Uuid;
 
+use crate::application::denormalization::denormalize;
 use crate::domain::post::Post;
 use crate::domain::post_repository::{PostRepository, PostRepositoryImpl};
 
-pub struct UpdatePostUseCase<'a> {
-	post_repository: Arc<PostRepositoryImpl<'a>>,
+pub struct UpdatePostUseCase<R = PostRepositoryImpl> {
+	post_repository: Arc<R>,
 }
 
-impl <'a> UpdatePostUseCase <'a> {
-	pub fn new(post_repository: Arc<PostRepositoryImpl<'a>>) -> Self {
+impl<R: PostRepository> UpdatePostUseCase<R> {
+	pub fn new(post_repository: Arc<R>) -> Self {
 		UpdatePostUseCase { post_repository }
 	}
 
-	pub async fn execute(&self, post_id: &Uuid, post: Json<Post>) -> Result<Custom<sea_orm::prelude::Json>, Custom<sea_orm::prelude::Json>> {
-		if !self.post_repository.check_if_exists(post_id).await {
+	pub async fn execute(&self, post_id: &Uuid, post: Json<Post>, tenant: &str) -> Result<Custom<sea_orm::prelude::Json>, Custom<sea_orm::prelude::Json>> {
+		self.post_repository.check_writable().await?;
+		if !self.post_repository.check_if_exists(post_id, tenant).await? {
 			return Err(Custom(Status::NotFound, json!({
 				"status": Status::NotFound,
 				"message": "Post not found"
 			})))
 		}
 
-		match self.post_repository.update(post_id, post).await {
-			Ok(response) => return Ok(response),
-			Err(e) => return Err(e)
-		};
+		let mut post = post.into_inner();
+		denormalize(self.post_repository.as_ref(), &mut post, tenant).await?;
+		let propagation = post.propagation();
+
+		let mut response = self.post_repository.update(post_id, Json(post), tenant).await?;
+
+		// Copies on other documents are refreshed in the background; progress can be
+		// followed through the returned task.
+		if let Some(body) = propagation {
+			response.1["propagation_task"] = json!(self.post_repository.start_update_by_query(body, tenant).await?);
+		}
+
+		Ok(response)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::infrastructure::repositories::in_memory_post_repository::InMemoryPostRepository;
+
+	fn category(id: Uuid, name: &str) -> Post {
+		serde_json::from_value(json!({"type": "Category", "id": id, "name": name})).unwrap()
+	}
+
+	fn course(id: Uuid, category_ids: &[Uuid]) -> Post {
+		serde_json::from_value(json!({
+			"type": "Course",
+			"id": id,
+			"name": "Rust",
+			"description": "Ownership and borrowing",
+			"picture": "rust.png",
+			"category_ids": category_ids
+		})).unwrap()
+	}
+
+	#[tokio::test]
+	async fn posts_of_other_tenants_are_not_found() {
+		let repository = Arc::new(InMemoryPostRepository::default());
+		let id = Uuid::new_v4();
+		repository.insert(&category(id, "Acme"), "acme");
+		let use_case = UpdatePostUseCase::new(repository.clone());
+
+		let result = use_case.execute(&id, Json(category(id, "Renamed")), "globex").await;
+
+		assert!(matches!(result, Err(Custom(status, _)) if status == Status::NotFound));
+		assert_eq!(repository.document(&id).unwrap()["name"], "Acme");
+		assert!(repository.updates_by_query.lock().unwrap().is_empty());
 	}
-}
\ No newline at end of file
+
+	#[tokio::test]
+	async fn updates_copy_only_categories_of_the_same_tenant() {
+		let repository = Arc::new(InMemoryPostRepository::default());
+		let (own, foreign, course_id) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+		repository.insert(&category(own, "Own"), "acme");
+		repository.insert(&category(foreign, "Foreign"), "globex");
+		repository.insert(&course(course_id, &[]), "acme");
+		let use_case = UpdatePostUseCase::new(repository.clone());
+
+		use_case.execute(&course_id, Json(course(course_id, &[own, foreign])), "acme").await.unwrap();
+
+		let document = repository.document(&course_id).unwrap();
+		assert_eq!(document["categories"], json!([{"id": own, "name": "Own"}]));
+		assert_eq!(document["tenant_id"], "acme");
+	}
+
+	#[tokio::test]
+	async fn propagation_is_scoped_to_the_callers_tenant() {
+		let repository = Arc::new(InMemoryPostRepository::default());
+		let id = Uuid::new_v4();
+		repository.insert(&category(id, "Acme"), "acme");
+		let use_case = UpdatePostUseCase::new(repository.clone());
+
+		let response = use_case.execute(&id, Json(category(id, "Renamed")), "acme").await.unwrap();
+
+		assert_eq!(response.1["propagation_task"], "memory:1");
+		let updates = repository.updates_by_query.lock().unwrap();
+		assert_eq!(updates.len(), 1);
+		assert_eq!(updates[0].1, "acme");
+		assert_eq!(updates[0].0["script"]["params"]["name"], "Renamed");
+	}
+
+	#[tokio::test]
+	async fn updates_during_a_migration_are_refused() {
+		let repository = Arc::new(InMemoryPostRepository::default());
+		let id = Uuid::new_v4();
+		repository.insert(&category(id, "Acme"), "acme");
+		repository.migrating.store(true, std::sync::atomic::Ordering::SeqCst);
+		let use_case = UpdatePostUseCase::new(repository.clone());
+
+		let result = use_case.execute(&id, Json(category(id, "Renamed")), "acme").await;
+
+		assert!(matches!(result, Err(Custom(status, _)) if status == Status::ServiceUnavailable));
+		assert_eq!(repository.document(&id).unwrap()["name"], "Acme");
+		assert!(repository.updates_by_query.lock().unwrap().is_empty());
+	}
+}
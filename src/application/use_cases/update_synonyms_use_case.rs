@@ -0,0 +1,35 @@
+use std::sync::Arc;
+use rocket::http::Status;
+use rocket::response::status::Custom;
+use rocket::serde::json::{Json, json};
+
+use crate::domain::post_repository::{PostRepository, PostRepositoryImpl};
+use crate::interfaces::dtos::SynonymsDto;
+
+pub struct UpdateSynonymsUseCase {
+	post_repository: Arc<PostRepositoryImpl>,
+}
+
+impl UpdateSynonymsUseCase {
+	pub fn new(post_repository: Arc<PostRepositoryImpl>) -> Self {
+		UpdateSynonymsUseCase { post_repository }
+	}
+
+	pub async fn execute(&self, synonyms: Json<SynonymsDto>) -> Result<Custom<sea_orm::prelude::Json>, Custom<sea_orm::prelude::Json>> {
+		let rules: Vec<String> = synonyms
+			.into_inner()
+			.synonyms
+			.into_iter()
+			.map(|rule| rule.trim().to_string())
+			.collect();
+
+		if rules.iter().any(|rule| rule.is_empty()) {
+			return Err(Custom(Status::BadRequest, json!({
+				"success": false,
+				"message": "Synonym rules must not be empty"
+			})))
+		}
+
+		self.post_repository.put_synonyms(&rules).await
+	}
+}
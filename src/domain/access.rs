@@ -0,0 +1,188 @@
+use rocket::serde::{Deserialize, Serialize};
+use serde_json::json;
+use uuid::Uuid;
+
+/// Who may see a post. Carried by every post and enforced on search. Fields left out of an
+/// update keep their stored value.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct Access {
+	/// Unset on posts that never named one, which are public.
+	#[serde(default)]
+	pub visibility: Option<Visibility>,
+	#[serde(default)]
+	pub owner_id: Option<Uuid>,
+	#[serde(default)]
+	pub tenant_id: Option<String>
+}
+
+/// Tenant of callers and posts that name none, including posts indexed before tenants
+/// existed.
+pub const DEFAULT_TENANT: &str = "default";
+
+impl Access {
+	pub fn tenant(&self) -> &str {
+		self.tenant_id.as_deref().unwrap_or(DEFAULT_TENANT)
+	}
+
+	/// Tenant ids end up in alias names, so they are restricted to lowercase letters,
+	/// digits, `-` and `_`.
+	pub fn is_valid_tenant(tenant: &str) -> bool {
+		!tenant.is_empty()
+			&& tenant.len() <= 64
+			&& tenant.bytes().all(|b| b.is_ascii_lowercase() || b.is_ascii_digit() || b == b'-' || b == b'_')
+	}
+
+	/// Matches the documents of `tenant`. Documents without a tenant belong to the default one.
+	pub fn tenant_filter(tenant: &str) -> sea_orm::prelude::Json {
+		if tenant != DEFAULT_TENANT {
+			return json!({ "term": { "tenant_id": tenant } });
+		}
+
+		json!({
+			"bool": {
+				"should": [
+					{ "term": { "tenant_id": tenant } },
+					{ "bool": { "must_not": { "exists": { "field": "tenant_id" } } } }
+				],
+				"minimum_should_match": 1
+			}
+		})
+	}
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Visibility {
+	#[default]
+	Public,
+	/// Only visible to its owner and to admins.
+	Private
+}
+
+/// What a caller may change. Searching needs no scope.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Scope {
+	/// Index, update and delete posts.
+	Index,
+	/// Manage the index itself: synonyms, tasks. Index-wide changes also need a service token.
+	Admin
+}
+
+impl Scope {
+	pub fn from_claim(claim: &str) -> Option<Scope> {
+		match claim {
+			"search:index" => Some(Scope::Index),
+			"search:admin" => Some(Scope::Admin),
+			_ => None
+		}
+	}
+}
+
+/// The identity behind a request. Requests without credentials are anonymous.
+#[derive(Debug, Clone, Default)]
+pub struct Caller {
+	pub user_id: Option<Uuid>,
+	pub admin: bool,
+	pub tenant_id: Option<String>,
+	pub scopes: Vec<Scope>,
+	/// Authenticated with a shared service token, which serves every tenant.
+	pub service: bool
+}
+
+impl Caller {
+	pub fn anonymous() -> Self {
+		Caller::default()
+	}
+
+	/// Another service authenticated with a shared service token.
+	pub fn service(scopes: Vec<Scope>) -> Self {
+		Caller {
+			admin: scopes.contains(&Scope::Admin),
+			scopes,
+			service: true,
+			..Caller::default()
+		}
+	}
+
+	pub fn is_anonymous(&self) -> bool {
+		self.user_id.is_none() && self.scopes.is_empty() && !self.admin
+	}
+
+	pub fn has_scope(&self, scope: Scope) -> bool {
+		self.scopes.contains(&scope)
+	}
+
+	/// The tenant whose posts this caller reads and writes. Never crossed, not even by admins.
+	pub fn tenant(&self) -> &str {
+		self.tenant_id.as_deref().unwrap_or(DEFAULT_TENANT)
+	}
+
+	/// Filter every search made by this caller must apply: private, unpublished and
+	/// deactivated posts are hidden unless the caller owns them. Admins see everything.
+	pub fn visibility_filter(&self) -> Option<sea_orm::prelude::Json> {
+		if self.admin {
+			return None;
+		}
+
+		let visible = json!({
+			"bool": {
+				"must_not": [
+					{ "term": { "visibility": "private" } },
+					{ "term": { "published": false } },
+					{ "term": { "active": false } }
+				]
+			}
+		});
+		let Some(user_id) = self.user_id else {
+			return Some(visible);
+		};
+
+		Some(json!({
+			"bool": {
+				"should": [
+					visible,
+					{ "term": { "owner_id": user_id } },
+					{ "term": { "instructor_id": user_id } },
+					{ "term": { "id": user_id } }
+				],
+				"minimum_should_match": 1
+			}
+		}))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn tenant_ids_are_restricted_to_alias_safe_characters() {
+		for tenant in ["acme", "team_1-b", "default", &"a".repeat(64)] {
+			assert!(Access::is_valid_tenant(tenant), "{} should be valid", tenant);
+		}
+		for tenant in ["", "Acme", "a b", "a/b", "a,b", "*", "acme*", "ácme", &"a".repeat(65)] {
+			assert!(!Access::is_valid_tenant(tenant), "{} should be invalid", tenant);
+		}
+	}
+
+	#[test]
+	fn tenant_filter_matches_only_the_tenants_documents() {
+		assert_eq!(Access::tenant_filter("acme"), json!({"term": {"tenant_id": "acme"}}));
+	}
+
+	#[test]
+	fn default_tenant_filter_also_matches_documents_without_a_tenant() {
+		let filter = Access::tenant_filter(DEFAULT_TENANT);
+		let should = filter["bool"]["should"].as_array().unwrap();
+		assert!(should.contains(&json!({"term": {"tenant_id": DEFAULT_TENANT}})));
+		assert!(should.contains(&json!({"bool": {"must_not": {"exists": {"field": "tenant_id"}}}})));
+		assert_eq!(filter["bool"]["minimum_should_match"], 1);
+	}
+
+	#[test]
+	fn documents_and_callers_without_a_tenant_belong_to_the_default_one() {
+		assert_eq!(Access::default().tenant(), DEFAULT_TENANT);
+		assert_eq!(Caller::anonymous().tenant(), DEFAULT_TENANT);
+		assert_eq!(Caller { tenant_id: Some("acme".into()), ..Caller::default() }.tenant(), "acme");
+	}
+}
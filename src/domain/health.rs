@@ -0,0 +1,29 @@
+use rocket::serde::Serialize;
+use serde_json::Value;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HealthStatus {
+	Up,
+	Down
+}
+
+/// Outcome of one readiness check, with whatever details help diagnose it.
+#[derive(Debug, Serialize)]
+pub struct HealthCheck {
+	#[serde(skip)]
+	pub name: &'static str,
+	pub status: HealthStatus,
+	#[serde(flatten)]
+	pub details: Value
+}
+
+impl HealthCheck {
+	pub fn new(name: &'static str, up: bool, details: Value) -> Self {
+		HealthCheck {
+			name,
+			status: if up { HealthStatus::Up } else { HealthStatus::Down },
+			details
+		}
+	}
+}
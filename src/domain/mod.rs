@@ -1,4 +1,7 @@
 // This file is intentionally left blank.
+pub mod access;
 pub mod entities;
+pub mod health;
 pub mod post;
-pub mod post_repository;
\ No newline at end of file
+pub mod post_repository;
+pub mod search;
\ No newline at end of file
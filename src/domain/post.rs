@@ -1,22 +1,38 @@
+use chrono::{DateTime, Utc};
 use rocket::serde::{ Serialize, Deserialize };
 use serde_json::json;
 use uuid::Uuid;
 
+use super::access::Access;
+
 // A Course
 #[derive(Debug, Serialize, Deserialize, PartialEq)]
 #[serde(tag = "type")]
 pub enum Post {
-	Course(Course),
+	Course(Box<Course>),
 	Category(Category),
 	User(User)
 }
 
 impl Post {
+	/// Fields that carry prose and get a stemmed subfield per `Language`.
+	const LANGUAGE_FIELDS: [&'static str; 2] = ["name", "description"];
+	/// Text fields with a `.sort` keyword subfield.
+	const SORTABLE_FIELDS: [&'static str; 1] = ["name"];
+
 	pub fn id(&self) -> Uuid {
 		match self {
-			Post::Course(co) => co.id.clone(),
-			Post::Category(ca) => ca.id.clone(),
-			Post::User(u) => u.id.clone()
+			Post::Course(co) => co.id,
+			Post::Category(ca) => ca.id,
+			Post::User(u) => u.id
+		}
+	}
+
+	pub fn access(&self) -> &Access {
+		match self {
+			Post::Course(co) => &co.access,
+			Post::Category(ca) => &ca.access,
+			Post::User(u) => &u.access
 		}
 	}
 
@@ -24,12 +40,34 @@ impl Post {
 		match self {
 			Post::Course(co) => json!({
 				"script": {
-					"source": "ctx._source.name = params.name; ctx._source.description = params.description ; ctx._source.picture = params.picture",
+					"source": concat!(
+						"ctx._source.name = params.name; ctx._source.description = params.description ; ctx._source.picture = params.picture; ctx._source.language = params.language; ",
+						"ctx._source.category_ids = params.category_ids; ctx._source.categories = params.categories; ctx._source.instructor_id = params.instructor_id; ctx._source.instructor = params.instructor; ctx._source.tags = params.tags; ctx._source.level = params.level; ",
+						"ctx._source.price = params.price; ctx._source.rating = params.rating; ctx._source.enrollment_count = params.enrollment_count; ",
+						"if (params.published != null) { ctx._source.published = params.published } ",
+						"if (params.created_at != null) { ctx._source.created_at = params.created_at } ctx._source.updated_at = params.updated_at; ",
+						"if (params.visibility != null) { ctx._source.visibility = params.visibility } if (params.owner_id != null) { ctx._source.owner_id = params.owner_id }"
+					),
 					"lang": "painless",
 					"params": {
 						"name": co.name,
 						"description": co.description,
-						"picture": co.picture
+						"picture": co.picture,
+						"language": co.language,
+						"category_ids": co.category_ids,
+						"categories": co.categories,
+						"instructor_id": co.instructor_id,
+						"instructor": co.instructor,
+						"tags": co.tags,
+						"level": co.level,
+						"price": co.price,
+						"rating": co.rating,
+						"enrollment_count": co.enrollment_count,
+						"published": co.published,
+						"created_at": co.created_at,
+						"updated_at": co.updated_at,
+						"visibility": co.access.visibility,
+						"owner_id": co.access.owner_id
 					}
 				},
 				"query": {
@@ -40,10 +78,12 @@ impl Post {
 			}),
 			Post::Category(ca) => json!({
 				"script": {
-					"source": "ctx._source.name = params.name",
+					"source": "ctx._source.name = params.name; if (params.visibility != null) { ctx._source.visibility = params.visibility } if (params.owner_id != null) { ctx._source.owner_id = params.owner_id }",
 					"lang": "painless",
 					"params": {
-						"name": ca.name
+						"name": ca.name,
+						"visibility": ca.access.visibility,
+						"owner_id": ca.access.owner_id
 					}
 				},
 				"query": {
@@ -54,13 +94,16 @@ impl Post {
 			}),
 			Post::User(u) => json!({
 				"script": {
-					"source": "ctx._source.name = params.name; ctx._source.nickname = params.nickname; ctx._source.lastname = params.lastname; ctx._source.picture = params.picture",
+					"source": "ctx._source.name = params.name; ctx._source.nickname = params.nickname; ctx._source.lastname = params.lastname; ctx._source.picture = params.picture; if (params.active != null) { ctx._source.active = params.active } if (params.visibility != null) { ctx._source.visibility = params.visibility } if (params.owner_id != null) { ctx._source.owner_id = params.owner_id }",
 					"lang": "painless",
 					"params": {
 						"name": u.name,
 						"lastname": u.lastname,
 						"nickname": u.nickname,
-						"picture": u.picture
+						"picture": u.picture,
+						"active": u.active,
+						"visibility": u.access.visibility,
+						"owner_id": u.access.owner_id
 					}
 				},
 				"query": {
@@ -72,29 +115,138 @@ impl Post {
 		}
 	}
 	
-	pub fn base_index(&self) -> sea_orm::prelude::Json {
-		json!({
+	/// Update-by-query refreshing the copies of this post denormalized onto other
+	/// documents, if any.
+	pub fn propagation(&self) -> Option<sea_orm::prelude::Json> {
+		match self {
+			Post::Category(ca) => Some(json!({
+				"script": {
+					"source": "if (ctx._source.categories != null) { for (category in ctx._source.categories) { if (category.id == params.id) { category.name = params.name } } }",
+					"lang": "painless",
+					"params": {
+						"id": ca.id,
+						"name": ca.name
+					}
+				},
+				"query": {
+					"bool": {
+						"filter": [
+							{ "term": { "type": "Course" } },
+							{ "term": { "category_ids": ca.id } }
+						]
+					}
+				}
+			})),
+			Post::User(u) => Some(json!({
+				"script": {
+					"source": "ctx._source.instructor = params.instructor",
+					"lang": "painless",
+					"params": {
+						"instructor": InstructorRef::from(u)
+					}
+				},
+				"query": {
+					"bool": {
+						"filter": [
+							{ "term": { "type": "Course" } },
+							{ "term": { "instructor_id": u.id } }
+						]
+					}
+				}
+			})),
+			Post::Course(_) => None
+		}
+	}
+
+	/// Index definition. `synonyms_set` names the Elasticsearch synonyms set applied
+	/// at search time; since it is `updateable`, edits only need an analyzer reload.
+	pub fn base_index(synonyms_set: &str) -> sea_orm::prelude::Json {
+		let mut index = json!({
 			"mappings": {
 				"properties": {
 					"id": {
-						"type": "text",
-						"analyzer": "keyword"
+						"type": "keyword"
 					},
-					"name": {
-						"type": "text",
-						"analyzer": "edge_ngram_analyzer"
+					"type": {
+						"type": "keyword"
 					},
-					"description": {
-						"type": "text",
-						"analyzer": "edge_ngram_analyzer"
+					"name": Post::text_mapping("name"),
+					"description": Post::text_mapping("description"),
+					"nickname": Post::text_mapping("nickname"),
+					"lastname": Post::text_mapping("lastname"),
+					"language": {
+						"type": "keyword"
 					},
-					"nickname": {
-						"type": "text",
-						"analyzer": "edge_ngram_analyzer"
+					"category_ids": {
+						"type": "keyword"
 					},
-					"lastname": {
-						"type": "text",
-						"analyzer": "edge_ngram_analyzer"
+					"categories": {
+						"properties": {
+							"id": {
+								"type": "keyword"
+							},
+							"name": Post::text_mapping("categories.name")
+						}
+					},
+					"instructor_id": {
+						"type": "keyword"
+					},
+					"instructor": {
+						"properties": {
+							"id": {
+								"type": "keyword"
+							},
+							"name": {
+								"type": "text"
+							},
+							"lastname": {
+								"type": "text"
+							},
+							"nickname": {
+								"type": "text"
+							},
+							"picture": {
+								"type": "keyword",
+								"index": false
+							}
+						}
+					},
+					"tags": {
+						"type": "keyword"
+					},
+					"level": {
+						"type": "keyword"
+					},
+					"price": {
+						"type": "scaled_float",
+						"scaling_factor": 100
+					},
+					"rating": {
+						"type": "float"
+					},
+					"enrollment_count": {
+						"type": "long"
+					},
+					"published": {
+						"type": "boolean"
+					},
+					"created_at": {
+						"type": "date"
+					},
+					"updated_at": {
+						"type": "date"
+					},
+					"active": {
+						"type": "boolean"
+					},
+					"visibility": {
+						"type": "keyword"
+					},
+					"owner_id": {
+						"type": "keyword"
+					},
+					"tenant_id": {
+						"type": "keyword"
 					},
 					"picture": {
 						"type": "text",
@@ -114,17 +266,36 @@ impl Post {
 				"analysis": {
 					"analyzer": {
 						"expand": {
-							"filter": ["lowercase"],
+							"filter": ["lowercase", "asciifolding"],
+							"tokenizer": "standard",
+							"type": "custom"
+						},
+						"synonym_analyzer": {
+							"filter": ["lowercase", "asciifolding", "synonyms"],
 							"tokenizer": "standard",
 							"type": "custom"
 						},
 						"edge_ngram_analyzer": {
 							"filter": [
-								"lowercase"
+								"lowercase",
+								"asciifolding"
 							],
 							"tokenizer": "edge_ngram_tokenizer"
 						}
 					},
+					"filter": {
+						"synonyms": {
+							"type": "synonym_graph",
+							"synonyms_set": synonyms_set,
+							"updateable": true
+						}
+					},
+					"normalizer": {
+						"sort_normalizer": {
+							"type": "custom",
+							"filter": ["lowercase", "asciifolding"]
+						}
+					},
 					"tokenizer": {
 						"edge_ngram_tokenizer": {
 							"type": "edge_ngram",
@@ -138,37 +309,227 @@ impl Post {
 					}
 				}
 			}
+		});
+
+		let analysis = &mut index["settings"]["analysis"];
+		for language in Language::ALL {
+			let (stop, stemmer) = (format!("{}_stop", language.code()), format!("{}_stemmer", language.code()));
+			analysis["filter"][&stop] = json!({
+				"type": "stop",
+				"stopwords": language.stopwords()
+			});
+			analysis["filter"][&stemmer] = json!({
+				"type": "stemmer",
+				"language": language.stemmer()
+			});
+			analysis["analyzer"][language.analyzer()] = json!({
+				"type": "custom",
+				"tokenizer": "standard",
+				"filter": ["lowercase", "asciifolding", stop, stemmer]
+			});
+		}
+
+		index
+	}
+
+	/// Fields of `base_index` that `mappings`, an existing index's mappings, leaves out or maps
+	/// with another type. Posts indexed into such an index would be mapped or searched wrongly.
+	pub fn mapping_conflicts(mappings: &sea_orm::prelude::Json) -> Vec<String> {
+		let expected = Post::base_index("");
+		let mut conflicts = Vec::new();
+		Post::compare_properties(&expected["mappings"]["properties"], &mappings["properties"], "", &mut conflicts);
+		conflicts
+	}
+
+	/// Compares fields recursively, through object `properties` and multi-`fields`.
+	fn compare_properties(expected: &sea_orm::prelude::Json, actual: &sea_orm::prelude::Json, prefix: &str, conflicts: &mut Vec<String>) {
+		let type_of = |field: &sea_orm::prelude::Json| field["type"].as_str().unwrap_or("object").to_string();
+		for (name, field) in expected.as_object().into_iter().flatten() {
+			let path = format!("{}{}", prefix, name);
+			let actual = &actual[name];
+			if actual.is_null() {
+				conflicts.push(format!("{} is not mapped", path));
+				continue;
+			}
+			if type_of(field) != type_of(actual) {
+				conflicts.push(format!("{} is mapped as {} instead of {}", path, type_of(actual), type_of(field)));
+				continue;
+			}
+			for nested in ["properties", "fields"] {
+				if !field[nested].is_null() {
+					Post::compare_properties(&field[nested], &actual[nested], &format!("{}.", path), conflicts);
+				}
+			}
+		}
+	}
+
+	/// Mapping for a free-text field: edge_ngram at index time for prefix matching, a
+	/// `.exact` subfield, a normalized `.sort` keyword for `SORTABLE_FIELDS`, and one
+	/// stemmed subfield per language for `LANGUAGE_FIELDS`.
+	/// Both the field and `.exact` expand synonyms at search time.
+	fn text_mapping(field: &str) -> sea_orm::prelude::Json {
+		let mut fields = json!({
+			"exact": {
+				"type": "text",
+				"analyzer": "expand",
+				"search_analyzer": "synonym_analyzer"
+			}
+		});
+		if Post::SORTABLE_FIELDS.contains(&field) {
+			fields["sort"] = json!({
+				"type": "keyword",
+				"normalizer": "sort_normalizer",
+				"ignore_above": 256
+			});
+		}
+		if Post::LANGUAGE_FIELDS.contains(&field) {
+			for language in Language::ALL {
+				fields[language.code()] = json!({
+					"type": "text",
+					"analyzer": language.analyzer()
+				});
+			}
+		}
+		json!({
+			"type": "text",
+			"analyzer": "edge_ngram_analyzer",
+			"search_analyzer": "synonym_analyzer",
+			"fields": fields
 		})
 	}
 
-	pub fn query(&self, q: &str) -> sea_orm::prelude::Json {
+	pub fn post_type(&self) -> PostType {
 		match self {
-			Post::Course(_) => json!({
-				"query": {
-					"multi_match": {
-						"query": q,
-						"fields": ["name", "description"]
+			Post::Course(_) => PostType::Course,
+			Post::Category(_) => PostType::Category,
+			Post::User(_) => PostType::User
+		}
+	}
+
+	pub fn query(&self, q: &str) -> sea_orm::prelude::Json {
+		json!({
+			"query": Post::text_query(q, self.post_type().text_fields())
+		})
+	}
+
+	/// Matches `q` against the edge_ngram `fields` for prefix hits and against their
+	/// `.exact` subfields, so whole-word matches rank above partial ones. Prose fields
+	/// are also matched through the stemmed subfield of each document's language.
+	pub fn text_query(q: &str, fields: &[&str]) -> sea_orm::prelude::Json {
+		let exact_fields: Vec<String> = fields
+			.iter()
+			.map(|f| format!("{}.exact", f))
+			.collect();
+		let language_queries = Language::ALL.iter().filter_map(|language| {
+			let language_fields: Vec<String> = fields
+				.iter()
+				.filter(|f| Post::LANGUAGE_FIELDS.contains(f))
+				.map(|f| format!("{}.{}", f, language.code()))
+				.collect();
+			if language_fields.is_empty() {
+				return None;
+			}
+			Some(json!({
+				"bool": {
+					"filter": language.filter(),
+					"must": {
+						"multi_match": {
+							"query": q,
+							"fields": language_fields,
+							"operator": "and",
+							"type": "cross_fields"
+						}
 					}
 				}
-			}),
-			Post::Category(_) => json!({
-				"query": {
-					"multi_match": {
-						"query": q,
-						"fields": ["name"]
-					}
+			}))
+		});
+		let mut should = vec![
+			json!({
+				"multi_match": {
+					"query": q,
+					"fields": fields,
+					"operator": "and",
+					"type": "cross_fields"
 				}
 			}),
-			Post::User(_) => json!({
-				"query": {
-					"multi_match": {
-						"query": q,
-						"fields": ["name", "lastname", "nickname"]
-					}
+			json!({
+				"multi_match": {
+					"query": q,
+					"fields": exact_fields,
+					"operator": "and",
+					"type": "cross_fields",
+					"boost": 2
 				}
 			})
+		];
+		should.extend(language_queries);
+		json!({
+			"bool": {
+				"should": should,
+				"minimum_should_match": 1
+			}
+		})
+	}
+}
+
+/// The kind of a `Post`, as stored in its `type` field.
+#[derive(Debug, Clone, Copy, PartialEq, FromFormField)]
+pub enum PostType {
+	Course,
+	Category,
+	User
+}
+
+impl PostType {
+	pub const ALL: [PostType; 3] = [PostType::Course, PostType::Category, PostType::User];
+
+	pub fn tag(&self) -> &'static str {
+		match self {
+			PostType::Course => "Course",
+			PostType::Category => "Category",
+			PostType::User => "User"
 		}
 	}
+
+	pub fn from_tag(tag: &str) -> Option<PostType> {
+		PostType::ALL.into_iter().find(|t| t.tag() == tag)
+	}
+
+	/// Source fields of this type that may be returned to clients. Anything else stored
+	/// on the document stays internal.
+	pub fn public_fields(&self) -> &'static [&'static str] {
+		match self {
+			PostType::Course => &[
+				"id", "type", "name", "description", "picture", "language", "category_ids", "categories",
+				"instructor_id", "instructor", "tags", "level", "price", "rating", "enrollment_count",
+				"published", "created_at", "updated_at"
+			],
+			PostType::Category => &["id", "type", "name"],
+			PostType::User => &["id", "type", "name", "lastname", "nickname", "picture"]
+		}
+	}
+
+	/// Free-text fields of this type, searched and highlighted.
+	pub fn text_fields(&self) -> &'static [&'static str] {
+		match self {
+			PostType::Course => &["name", "description", "categories.name"],
+			PostType::Category => &["name"],
+			PostType::User => &["name", "lastname", "nickname"]
+		}
+	}
+
+	/// Free-text fields of `post_type`, or of every type when it is not given.
+	pub fn text_fields_of(post_type: Option<PostType>) -> Vec<&'static str> {
+		let mut fields: Vec<&'static str> = Vec::new();
+		for t in post_type.map_or(PostType::ALL.to_vec(), |t| vec![t]) {
+			for field in t.text_fields() {
+				if !fields.contains(field) {
+					fields.push(field);
+				}
+			}
+		}
+		fields
+	}
 }
 
 #[derive(Debug, Serialize, Deserialize, PartialEq)]
@@ -176,19 +537,131 @@ pub struct Course {
 	pub id: Uuid,
 	pub name: String,
 	pub description: String,
-	pub picture: String
+	pub picture: String,
+	#[serde(default)]
+	pub language: Language,
+	#[serde(default)]
+	pub category_ids: Vec<Uuid>,
+	/// Copies of the referenced categories, filled in from `category_ids` on write.
+	#[serde(default)]
+	pub categories: Vec<CategoryRef>,
+	#[serde(default)]
+	pub instructor_id: Option<Uuid>,
+	/// Copy of the instructor's profile, filled in from `instructor_id` on write.
+	#[serde(default)]
+	pub instructor: Option<InstructorRef>,
+	#[serde(default)]
+	pub tags: Vec<String>,
+	#[serde(default)]
+	pub level: Option<Level>,
+	/// Unset on courses without a price, which price filters and facets then leave out.
+	#[serde(default)]
+	pub price: Option<f64>,
+	#[serde(default)]
+	pub rating: f64,
+	#[serde(default)]
+	pub enrollment_count: u64,
+	/// Unset on courses indexed before `published` existed, which were all publicly listed.
+	#[serde(default)]
+	pub published: Option<bool>,
+	#[serde(default)]
+	pub created_at: Option<DateTime<Utc>>,
+	#[serde(default)]
+	pub updated_at: Option<DateTime<Utc>>,
+	#[serde(flatten)]
+	pub access: Access
 }
 
 impl From<Course> for Post {
 	fn from(c: Course) -> Self {
-		Post::Course(c)
+		Post::Course(Box::new(c))
+	}
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, FromFormField)]
+#[serde(rename_all = "lowercase")]
+pub enum Level {
+	Beginner,
+	Intermediate,
+	Advanced
+}
+
+/// Content language of a document, selecting the stemmed subfields it is matched on.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, FromFormField)]
+pub enum Language {
+	#[default]
+	#[serde(rename = "es")]
+	#[field(value = "es")]
+	Spanish,
+	#[serde(rename = "en")]
+	#[field(value = "en")]
+	English
+}
+
+impl Language {
+	pub const ALL: [Language; 2] = [Language::Spanish, Language::English];
+
+	pub fn code(&self) -> &'static str {
+		match self {
+			Language::Spanish => "es",
+			Language::English => "en"
+		}
+	}
+
+	pub fn analyzer(&self) -> String {
+		format!("{}_analyzer", self.code())
+	}
+
+	fn stopwords(&self) -> &'static str {
+		match self {
+			Language::Spanish => "_spanish_",
+			Language::English => "_english_"
+		}
+	}
+
+	fn stemmer(&self) -> &'static str {
+		match self {
+			Language::Spanish => "light_spanish",
+			Language::English => "light_english"
+		}
+	}
+
+	/// Documents written in this language. Documents without a `language` field
+	/// (categories, users) are treated as written in the default language.
+	pub fn filter(&self) -> sea_orm::prelude::Json {
+		let term = json!({
+			"term": {
+				"language": self.code()
+			}
+		});
+		if *self != Language::default() {
+			return term;
+		}
+		json!({
+			"bool": {
+				"should": [
+					term,
+					{
+						"bool": {
+							"must_not": {
+								"exists": {
+									"field": "language"
+								}
+							}
+						}
+					}
+				]
+			}
+		})
 	}
 }
 
 #[derive(Debug, Serialize, Deserialize, PartialEq)]
 pub struct Category {
 	pub id: Uuid,
-	pub name: String
+	pub name: String,
+	#[serde(flatten)]
+	pub access: Access
 }
 
 impl From<Category> for Post {
@@ -197,6 +670,19 @@ impl From<Category> for Post {
 	}
 }
 
+/// A category as denormalized onto the courses that belong to it.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct CategoryRef {
+	pub id: Uuid,
+	pub name: String
+}
+
+impl From<&Category> for CategoryRef {
+	fn from(c: &Category) -> Self {
+		CategoryRef { id: c.id, name: c.name.clone() }
+	}
+}
+
 #[derive(Debug, Serialize, Deserialize, PartialEq)]
 pub struct User {
 	pub id: Uuid,
@@ -204,10 +690,147 @@ pub struct User {
 	pub lastname: String,
 	pub nickname: String,
 	pub picture: String,
+	/// Unset on users that were never deactivated.
+	#[serde(default)]
+	pub active: Option<bool>,
+	#[serde(flatten)]
+	pub access: Access
 }
 
 impl From<User> for Post {
 	fn from(u: User) -> Self {
 		Post::User(u)
 	}
-}
\ No newline at end of file
+}
+
+/// A user as denormalized onto the courses they teach.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct InstructorRef {
+	pub id: Uuid,
+	pub name: String,
+	pub lastname: String,
+	pub nickname: String,
+	pub picture: String
+}
+
+impl From<&User> for InstructorRef {
+	fn from(u: &User) -> Self {
+		InstructorRef {
+			id: u.id,
+			name: u.name.clone(),
+			lastname: u.lastname.clone(),
+			nickname: u.nickname.clone(),
+			picture: u.picture.clone()
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use serde_json::Value;
+
+	use super::*;
+	use crate::domain::access::{Caller, Visibility};
+
+	/// Runs an update script the way Painless would, for the plain and `!= null` guarded
+	/// assignments the scripts are made of.
+	fn run(script: &Value, document: &mut Value) {
+		let mut guard: Option<&str> = None;
+		for statement in script["source"].as_str().unwrap().split(['{', '}', ';']).map(str::trim) {
+			if let Some(condition) = statement.strip_prefix("if (params.") {
+				guard = condition.strip_suffix(" != null)");
+			} else if let Some(assignment) = statement.strip_prefix("ctx._source.") {
+				let (field, param) = assignment.split_once(" = params.").unwrap();
+				let value = &script["params"][param];
+				if guard != Some(param) || !value.is_null() {
+					document[field] = value.clone();
+				}
+				guard = None;
+			}
+		}
+	}
+
+	fn hidden_from_anonymous(document: &Value) -> bool {
+		let filter = Caller::anonymous().visibility_filter().unwrap();
+		filter["bool"]["must_not"].as_array().unwrap().iter().any(|clause| {
+			let (field, value) = clause["term"].as_object().unwrap().iter().next().unwrap();
+			&document[field] == value
+		})
+	}
+
+	#[test]
+	fn updates_that_omit_access_fields_keep_private_and_unpublished_courses_hidden() {
+		let id = Uuid::new_v4();
+		let owner = Uuid::new_v4();
+		let mut document = json!({
+			"type": "Course",
+			"id": id,
+			"name": "Rust",
+			"published": false,
+			"visibility": "private",
+			"owner_id": owner
+		});
+		let update: Post = serde_json::from_value(json!({
+			"type": "Course",
+			"id": id,
+			"name": "Rust 2024",
+			"description": "Ownership and borrowing",
+			"picture": "rust.png"
+		})).unwrap();
+
+		run(&update.update_by_query()["script"], &mut document);
+
+		assert_eq!(document["name"], "Rust 2024");
+		assert_eq!(document["published"], false);
+		assert_eq!(document["visibility"], "private");
+		assert_eq!(document["owner_id"], json!(owner));
+		assert!(hidden_from_anonymous(&document));
+	}
+
+	#[test]
+	fn updates_that_omit_access_fields_keep_deactivated_users_hidden() {
+		let id = Uuid::new_v4();
+		let mut document = json!({"type": "User", "id": id, "name": "Ada", "active": false});
+		let update: Post = serde_json::from_value(json!({
+			"type": "User",
+			"id": id,
+			"name": "Ada",
+			"lastname": "Lovelace",
+			"nickname": "ada",
+			"picture": "ada.png"
+		})).unwrap();
+
+		run(&update.update_by_query()["script"], &mut document);
+
+		assert_eq!(document["lastname"], "Lovelace");
+		assert_eq!(document["active"], false);
+		assert!(hidden_from_anonymous(&document));
+	}
+
+	#[test]
+	fn updates_that_set_access_fields_apply_them() {
+		let id = Uuid::new_v4();
+		let mut document = json!({"type": "Category", "id": id, "name": "Rust", "visibility": "private"});
+		let update: Post = serde_json::from_value(json!({"type": "Category", "id": id, "name": "Rust", "visibility": "public"})).unwrap();
+
+		run(&update.update_by_query()["script"], &mut document);
+
+		assert_eq!(document["visibility"], json!(Visibility::Public));
+		assert!(!hidden_from_anonymous(&document));
+	}
+
+	#[test]
+	fn courses_without_a_price_are_indexed_without_one() {
+		let course: Post = serde_json::from_value(json!({
+			"type": "Course",
+			"id": Uuid::new_v4(),
+			"name": "Rust",
+			"description": "Ownership and borrowing",
+			"picture": "rust.png"
+		})).unwrap();
+
+		// Elasticsearch indexes null as a missing value, which range filters and aggregations skip.
+		assert_eq!(json!(course)["price"], Value::Null);
+		assert_eq!(course.update_by_query()["script"]["params"]["price"], Value::Null);
+	}
+}
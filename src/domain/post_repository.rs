@@ -1,38 +1,115 @@
-use elasticsearch::Elasticsearch;
+use std::{collections::HashSet, sync::{atomic::AtomicBool, Arc, Mutex}};
+
 use rocket::{response::status::Custom, serde::json::Json};
 use serde_json::Value;
 use uuid::Uuid;
 
-use crate::infrastructure::client::create_client;
+use crate::infrastructure::{client::LazyClient, config::Settings, metrics::Metrics, resilience::Resilience};
 
-use super::post::Post;
+use super::{health::HealthCheck, post::Post, search::Projection};
 
 #[derive(Debug, serde::Deserialize, serde::Serialize)]
 pub struct PostResult {
-	pub post: Post,
+	pub post: Value,
 	pub highlight: Value,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub explanation: Option<Value>,
+}
+
+#[derive(Debug)]
+pub struct SearchResult {
+	pub posts: Vec<PostResult>,
+	pub aggregations: Value,
 }
 
+/// A copy of every post into a new index, which then replaces the current one.
+#[derive(Debug, Clone)]
+pub struct Migration {
+	pub source: String,
+	pub target: String,
+	/// The reindex task copying the posts.
+	pub task: String,
+}
+
+// Only implemented and called within this crate, so callers know which futures are `Send`.
+#[allow(async_fn_in_trait)]
 pub trait PostRepository {
-	async fn search(&self, query: sea_orm::prelude::Json) -> Result<Custom<sea_orm::prelude::Json>, Custom<sea_orm::prelude::Json>>;
+	async fn search(&self, query: sea_orm::prelude::Json, projection: &Projection, tenant: &str) -> Result<SearchResult, Custom<sea_orm::prelude::Json>>;
 	async fn set_refresh_interval(&self, interval: sea_orm::prelude::Json) -> Result<(), Custom<sea_orm::prelude::Json>>;
-	async fn check_if_exists(&self, post_id: &Uuid) -> bool;
-	async fn create_index_if_not_exists(&self, delete: bool, post: &Json<Post>) -> Result<(), Custom<sea_orm::prelude::Json>>;
-	async fn index(&self, posts: &[Post]) ->  Result<String, Custom<sea_orm::prelude::Json>>;
-	async fn update(&self, post_id: &Uuid, post: Json<Post>) ->  Result<Custom<sea_orm::prelude::Json>, Custom<sea_orm::prelude::Json>>;
-	async fn delete(&self, post_id: &Uuid) ->  Result<Custom<sea_orm::prelude::Json>, Custom<sea_orm::prelude::Json>>;
+	async fn check_if_exists(&self, post_id: &Uuid, tenant: &str) -> Result<bool, Custom<sea_orm::prelude::Json>>;
+	async fn create_index_if_not_exists(&self, delete: bool) -> Result<(), Custom<sea_orm::prelude::Json>>;
+	async fn index(&self, posts: &[Post], tenant: &str) ->  Result<String, Custom<sea_orm::prelude::Json>>;
+	async fn update(&self, post_id: &Uuid, post: Json<Post>, tenant: &str) ->  Result<Custom<sea_orm::prelude::Json>, Custom<sea_orm::prelude::Json>>;
+	async fn delete(&self, post_id: &Uuid, tenant: &str) ->  Result<Custom<sea_orm::prelude::Json>, Custom<sea_orm::prelude::Json>>;
+	async fn get_posts(&self, ids: &[Uuid], tenant: &str) -> Result<Vec<Post>, Custom<sea_orm::prelude::Json>>;
+	async fn start_update_by_query(&self, body: sea_orm::prelude::Json, tenant: &str) -> Result<String, Custom<sea_orm::prelude::Json>>;
+	/// With a `tenant`, only tasks started for that tenant are found.
+	async fn get_task(&self, task_id: &str, tenant: Option<&str>) -> Result<Custom<sea_orm::prelude::Json>, Custom<sea_orm::prelude::Json>>;
+	/// Whether the index maps `field`. Indices created before the field was added lack it until
+	/// they are migrated.
+	async fn maps_field(&self, field: &str) -> Result<bool, Custom<sea_orm::prelude::Json>>;
+	/// Fails with 503 while a migration copies the posts, as writes to the index would not be
+	/// copied.
+	async fn check_writable(&self) -> Result<(), Custom<sea_orm::prelude::Json>>;
+	/// Creates the next version of the index, with the current mappings and analysis, and starts
+	/// copying every post into it. The current index refuses writes from then on, and another
+	/// migration is refused with 409.
+	async fn start_reindex(&self) -> Result<Migration, Custom<sea_orm::prelude::Json>>;
+	/// Points the index name and the tenant aliases at the migration's target in one step.
+	async fn switch_index(&self, migration: &Migration) -> Result<(), Custom<sea_orm::prelude::Json>>;
+	/// Lets the source of a migration that will not switch take writes again. Its target is left
+	/// behind.
+	async fn abort_reindex(&self, migration: &Migration) -> Result<(), Custom<sea_orm::prelude::Json>>;
+	async fn get_synonyms(&self) -> Result<Vec<String>, Custom<sea_orm::prelude::Json>>;
+	async fn put_synonyms(&self, rules: &[String]) -> Result<Custom<sea_orm::prelude::Json>, Custom<sea_orm::prelude::Json>>;
+	async fn client_health(&self) -> HealthCheck;
+	/// Whether the cluster answers and is not red, and whether the index, its tenant aliases and
+	/// its mappings are as this service expects.
+	async fn cluster_health(&self) -> Vec<HealthCheck>;
 }
 
-pub struct PostRepositoryImpl<'a> {
-	pub client: Result<Elasticsearch, elasticsearch::Error>,
-	pub index: &'a str 
+/// Every operation on posts is scoped to one tenant: reads and by-query writes go through
+/// the tenant's filtered alias, lookups by id discard other tenants' documents and indexed
+/// posts are stamped with the tenant.
+pub struct PostRepositoryImpl {
+	pub client: LazyClient,
+	pub resilience: Resilience,
+	pub index: String,
+	/// Tenants whose alias is known to exist.
+	pub tenant_aliases: Mutex<HashSet<String>>,
+	pub max_tenant_aliases: usize,
+	/// Fields the index is known to map. Mappings only gain fields, so these stay mapped.
+	pub mapped_fields: Mutex<HashSet<String>>,
+	/// Whether outdated mappings were reported already.
+	pub mapping_warned: AtomicBool
 }
 
-impl <'a> PostRepositoryImpl <'a> {
-	pub fn new() -> Self {
+impl PostRepositoryImpl {
+	pub fn new(settings: &Settings, metrics: Arc<Metrics>) -> Self {
 		PostRepositoryImpl {
-			client: create_client(),
-			index: "posts"
+			client: LazyClient::new(settings.elasticsearch.clone()),
+			resilience: Resilience::new(&settings.elasticsearch, metrics),
+			index: settings.index.name.clone(),
+			tenant_aliases: Mutex::new(HashSet::new()),
+			max_tenant_aliases: settings.tenants.max_aliases,
+			mapped_fields: Mutex::new(HashSet::new()),
+			mapping_warned: AtomicBool::new(false)
 		}
 	}
+
+	/// Posts are kept in versioned indices behind an alias with the configured name, so they can
+	/// move to an index with other mappings without the name changing.
+	pub fn versioned_index(&self, version: u32) -> String {
+		format!("{}-v{}", self.index, version)
+	}
+
+	/// Name of the alias exposing only `tenant`'s documents.
+	pub fn tenant_alias(&self, tenant: &str) -> String {
+		format!("{}-tenant-{}", self.index, tenant)
+	}
+
+	/// Name of the synonyms set backing the index's search-time synonym filter.
+	pub fn synonyms_set(&self) -> String {
+		format!("{}-synonyms", self.index)
+	}
 }
\ No newline at end of file
@@ -0,0 +1,496 @@
+use std::str::FromStr;
+
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+
+use super::post::PostType;
+
+/// A facet the catalog can request alongside search results.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Facet {
+	Category,
+	Level,
+	Language,
+	Price
+}
+
+impl Facet {
+	pub fn name(&self) -> &'static str {
+		match self {
+			Facet::Category => "category",
+			Facet::Level => "level",
+			Facet::Language => "language",
+			Facet::Price => "price"
+		}
+	}
+
+	pub fn field(&self) -> &'static str {
+		match self {
+			Facet::Category => "category_ids",
+			Facet::Level => "level",
+			Facet::Language => "language",
+			Facet::Price => "price"
+		}
+	}
+
+	/// Parses a comma separated list such as `category,level`.
+	pub fn parse_list(facets: &str) -> Result<Vec<Facet>, String> {
+		facets
+			.split(',')
+			.map(str::trim)
+			.filter(|f| !f.is_empty())
+			.map(Facet::from_str)
+			.collect()
+	}
+
+	/// Aggregation for this facet. Active filters are applied as a `post_filter`, so each
+	/// facet re-applies every filter except its own: its buckets then count what selecting
+	/// another value would return.
+	pub fn aggregation(&self, active_filters: &[(Facet, sea_orm::prelude::Json)]) -> sea_orm::prelude::Json {
+		let other_filters: Vec<&sea_orm::prelude::Json> = active_filters
+			.iter()
+			.filter(|(facet, _)| facet != self)
+			.map(|(_, filter)| filter)
+			.collect();
+		let values = match self {
+			Facet::Price => json!({
+				"range": {
+					"field": self.field(),
+					"ranges": [
+						{ "key": "under_20", "to": 20 },
+						{ "key": "20_50", "from": 20, "to": 50 },
+						{ "key": "50_100", "from": 50, "to": 100 },
+						{ "key": "100_plus", "from": 100 }
+					]
+				}
+			}),
+			_ => json!({
+				"terms": {
+					"field": self.field(),
+					"size": 50
+				}
+			})
+		};
+		json!({
+			"filter": {
+				"bool": {
+					"filter": other_filters
+				}
+			},
+			"aggs": {
+				"values": values
+			}
+		})
+	}
+}
+
+impl FromStr for Facet {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s {
+			"category" => Ok(Facet::Category),
+			"level" => Ok(Facet::Level),
+			"language" => Ok(Facet::Language),
+			"price" => Ok(Facet::Price),
+			_ => Err(format!("Unknown facet: {}", s))
+		}
+	}
+}
+
+/// Result ordering. Every order ends on `id` so pages are stable between requests.
+#[derive(Debug, Clone, Copy, Default, PartialEq, FromFormField)]
+pub enum SortOption {
+	#[default]
+	Relevance,
+	Name,
+	Newest,
+	Rating,
+	Popularity
+}
+
+impl SortOption {
+	/// Orders valid when no type filter narrows the results.
+	const COMMON: [SortOption; 2] = [SortOption::Relevance, SortOption::Name];
+
+	/// Orders available for results of `post_type`; the rest need fields only courses have.
+	pub fn allowed_for(post_type: Option<PostType>) -> &'static [SortOption] {
+		match post_type {
+			Some(PostType::Course) => &[
+				SortOption::Relevance,
+				SortOption::Name,
+				SortOption::Newest,
+				SortOption::Rating,
+				SortOption::Popularity
+			],
+			Some(PostType::Category) | Some(PostType::User) | None => &SortOption::COMMON
+		}
+	}
+
+	pub fn name(&self) -> &'static str {
+		match self {
+			SortOption::Relevance => "relevance",
+			SortOption::Name => "name",
+			SortOption::Newest => "newest",
+			SortOption::Rating => "rating",
+			SortOption::Popularity => "popularity"
+		}
+	}
+
+	/// Sorts name their field's type as `unmapped_type`, so indices created before the field was
+	/// added sort as if every post lacked it instead of failing.
+	pub fn clause(&self) -> sea_orm::prelude::Json {
+		let primary = match self {
+			SortOption::Relevance => json!({"_score": {"order": "desc"}}),
+			SortOption::Name => json!({"name.sort": {"order": "asc", "missing": "_last", "unmapped_type": "keyword"}}),
+			SortOption::Newest => json!({"created_at": {"order": "desc", "missing": "_last", "unmapped_type": "date"}}),
+			SortOption::Rating => json!({"rating": {"order": "desc", "missing": "_last", "unmapped_type": "float"}}),
+			SortOption::Popularity => json!({"enrollment_count": {"order": "desc", "missing": "_last", "unmapped_type": "long"}})
+		};
+		json!([primary, {"id": {"order": "asc", "unmapped_type": "keyword"}}])
+	}
+}
+
+/// Weights blending popularity, rating and recency into the relevance score.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct RankingWeights {
+	pub popularity: f64,
+	pub rating: f64,
+	pub recency: f64,
+	/// Age at which the recency boost has decayed to half, e.g. `180d`.
+	pub recency_scale: String
+}
+
+impl Default for RankingWeights {
+	fn default() -> Self {
+		RankingWeights {
+			popularity: 1.0,
+			rating: 1.0,
+			recency: 1.0,
+			recency_scale: "180d".into()
+		}
+	}
+}
+
+impl RankingWeights {
+	/// Wraps `query` so its BM25 score is summed with the weighted popularity, rating and
+	/// recency of the document. Functions with a non-positive weight are left out, and so is
+	/// recency unless `created_at_mapped`: Elasticsearch refuses decay functions on unmapped
+	/// fields.
+	pub fn function_score(&self, query: sea_orm::prelude::Json, created_at_mapped: bool) -> sea_orm::prelude::Json {
+		let functions: Vec<sea_orm::prelude::Json> = [
+			(self.popularity, json!({
+				"field_value_factor": {
+					"field": "enrollment_count",
+					"modifier": "log1p",
+					"missing": 0
+				}
+			})),
+			(self.rating, json!({
+				"field_value_factor": {
+					"field": "rating",
+					"modifier": "log1p",
+					"missing": 0
+				}
+			})),
+			(if created_at_mapped { self.recency } else { 0.0 }, json!({
+				"filter": {
+					"exists": {
+						"field": "created_at"
+					}
+				},
+				"gauss": {
+					"created_at": {
+						"origin": "now",
+						"scale": self.recency_scale,
+						"decay": 0.5
+					}
+				}
+			}))
+		]
+			.into_iter()
+			.filter(|(weight, _)| *weight > 0.0)
+			.map(|(weight, mut function)| {
+				function["weight"] = json!(weight);
+				function
+			})
+			.collect();
+
+		if functions.is_empty() {
+			return query;
+		}
+
+		json!({
+			"function_score": {
+				"query": query,
+				"functions": functions,
+				"score_mode": "sum",
+				"boost_mode": "sum"
+			}
+		})
+	}
+}
+
+/// How highlight fragments treat the text around the highlight tags. Fragments quote
+/// user-authored content, so they are HTML-escaped unless raw output is asked for.
+#[derive(Debug, Clone, Copy, Default, PartialEq, FromFormField)]
+pub enum HighlightEncoder {
+	/// Text is returned as stored.
+	Raw,
+	/// Text is HTML-escaped before the tags are inserted.
+	#[default]
+	Html
+}
+
+impl HighlightEncoder {
+	/// Tag pairs allowed around highlights in escaped output; empty tags suit plain-text
+	/// clients.
+	const SAFE_TAGS: [(&'static str, &'static str); 5] = [
+		("<b>", "</b>"),
+		("<em>", "</em>"),
+		("<mark>", "</mark>"),
+		("<strong>", "</strong>"),
+		("", "")
+	];
+
+	/// Whether `pre_tag`/`post_tag` may be used. Escaped output only accepts the safe tag
+	/// pairs, as the tags themselves are inserted unescaped.
+	pub fn allows_tags(&self, pre_tag: &str, post_tag: &str) -> bool {
+		match self {
+			HighlightEncoder::Raw => true,
+			HighlightEncoder::Html => HighlightEncoder::SAFE_TAGS.contains(&(pre_tag, post_tag))
+		}
+	}
+
+	/// The Elasticsearch `encoder` setting.
+	pub fn encoder(&self) -> &'static str {
+		match self {
+			HighlightEncoder::Raw => "default",
+			HighlightEncoder::Html => "html"
+		}
+	}
+}
+
+/// Source fields returned for each hit: the requested fields, or every public field,
+/// limited to the public fields of the hit's type.
+#[derive(Debug, Default)]
+pub struct Projection {
+	fields: Option<Vec<String>>
+}
+
+impl Projection {
+	/// Fields every hit keeps, so clients can tell hits apart.
+	const ALWAYS: [&'static str; 2] = ["id", "type"];
+
+	/// Parses a comma separated list such as `name,picture`.
+	pub fn parse(fields: Option<&str>) -> Result<Self, String> {
+		let Some(fields) = fields else {
+			return Ok(Projection::default());
+		};
+
+		let fields: Vec<String> = fields
+			.split(',')
+			.map(str::trim)
+			.filter(|f| !f.is_empty())
+			.map(String::from)
+			.collect();
+		if let Some(unknown) = fields
+			.iter()
+			.find(|f| !PostType::ALL.iter().any(|t| t.public_fields().contains(&f.as_str())))
+		{
+			return Err(format!("Unknown field: {}", unknown));
+		}
+
+		Ok(Projection { fields: Some(fields) })
+	}
+
+	fn includes(&self, field: &str) -> bool {
+		Projection::ALWAYS.contains(&field)
+			|| self.fields.as_ref().is_none_or(|fields| fields.iter().any(|f| f == field))
+	}
+
+	/// `_source.includes` for the search request.
+	pub fn source_includes(&self) -> Vec<&'static str> {
+		let mut includes: Vec<&'static str> = Vec::new();
+		for post_type in PostType::ALL {
+			for field in post_type.public_fields() {
+				if self.includes(field) && !includes.contains(field) {
+					includes.push(field);
+				}
+			}
+		}
+		includes
+	}
+
+	/// Keeps the fields of `source` that are both requested and public for its type.
+	pub fn apply(&self, source: &Value) -> Value {
+		let Some(post_type) = source["type"].as_str().and_then(PostType::from_tag) else {
+			return json!({});
+		};
+
+		let projected: Map<String, Value> = post_type
+			.public_fields()
+			.iter()
+			.filter(|field| self.includes(field))
+			.filter_map(|field| source.get(*field).map(|value| (field.to_string(), value.clone())))
+			.collect();
+		Value::Object(projected)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn facets_parse_from_a_comma_separated_list() {
+		assert_eq!(Facet::parse_list("category, price,").unwrap(), vec![Facet::Category, Facet::Price]);
+		assert_eq!(Facet::parse_list("").unwrap(), vec![]);
+		assert_eq!(Facet::parse_list("level,rating").unwrap_err(), "Unknown facet: rating");
+	}
+
+	#[test]
+	fn facet_aggregations_apply_every_active_filter_but_their_own() {
+		let level = json!({"terms": {"level": ["beginner"]}});
+		let price = json!({"range": {"price": {"gte": 10}}});
+		let active = [(Facet::Level, level.clone()), (Facet::Price, price.clone())];
+
+		assert_eq!(Facet::Level.aggregation(&active)["filter"]["bool"]["filter"], json!([price]));
+		assert_eq!(Facet::Price.aggregation(&active)["filter"]["bool"]["filter"], json!([level]));
+		assert_eq!(Facet::Category.aggregation(&active)["filter"]["bool"]["filter"], json!([level, price]));
+	}
+
+	#[test]
+	fn facet_aggregations_bucket_terms_or_price_ranges() {
+		assert_eq!(Facet::Category.aggregation(&[])["aggs"]["values"], json!({"terms": {"field": "category_ids", "size": 50}}));
+
+		let price = Facet::Price.aggregation(&[]);
+		assert_eq!(price["aggs"]["values"]["range"]["field"], "price");
+		let keys: Vec<&str> = price["aggs"]["values"]["range"]["ranges"]
+			.as_array()
+			.unwrap()
+			.iter()
+			.map(|range| range["key"].as_str().unwrap())
+			.collect();
+		assert_eq!(keys, ["under_20", "20_50", "50_100", "100_plus"]);
+	}
+
+	#[test]
+	fn course_only_sorts_need_a_course_filter() {
+		for post_type in [None, Some(PostType::Category), Some(PostType::User)] {
+			assert_eq!(SortOption::allowed_for(post_type), [SortOption::Relevance, SortOption::Name]);
+		}
+		let course_sorts = SortOption::allowed_for(Some(PostType::Course));
+		for sort in [SortOption::Relevance, SortOption::Name, SortOption::Newest, SortOption::Rating, SortOption::Popularity] {
+			assert!(course_sorts.contains(&sort), "{} should be allowed on courses", sort.name());
+		}
+	}
+
+	#[test]
+	fn sorts_end_on_the_id() {
+		assert_eq!(SortOption::Rating.clause(), json!([
+			{"rating": {"order": "desc", "missing": "_last", "unmapped_type": "float"}},
+			{"id": {"order": "asc", "unmapped_type": "keyword"}}
+		]));
+	}
+
+	#[test]
+	fn sorts_tolerate_unmapped_fields() {
+		for sort in [SortOption::Name, SortOption::Newest, SortOption::Rating, SortOption::Popularity] {
+			for clause in sort.clause().as_array().unwrap() {
+				let (_, options) = clause.as_object().unwrap().iter().next().unwrap();
+				assert!(options["unmapped_type"].is_string(), "{} should name unmapped types", sort.name());
+			}
+		}
+	}
+
+	#[test]
+	fn relevance_blends_in_the_weighted_signals() {
+		let query = json!({"match_all": {}});
+		let scored = RankingWeights::default().function_score(query.clone(), true);
+		assert_eq!(scored["function_score"]["query"], query);
+		assert_eq!(scored["function_score"]["functions"].as_array().unwrap().len(), 3);
+		assert_eq!(scored["function_score"]["functions"][2]["gauss"]["created_at"]["scale"], "180d");
+
+		let weights = RankingWeights { popularity: 0.0, rating: 0.0, recency: 0.0, ..RankingWeights::default() };
+		assert_eq!(weights.function_score(query.clone(), true), query);
+	}
+
+	#[test]
+	fn recency_is_left_out_when_created_at_is_unmapped() {
+		let scored = RankingWeights::default().function_score(json!({"match_all": {}}), false);
+		let functions = scored["function_score"]["functions"].as_array().unwrap();
+
+		assert_eq!(functions.len(), 2);
+		assert!(functions.iter().all(|function| function.get("gauss").is_none()));
+	}
+
+	#[test]
+	fn escaped_highlights_only_accept_safe_tag_pairs() {
+		for (pre_tag, post_tag) in [("<b>", "</b>"), ("<em>", "</em>"), ("<mark>", "</mark>"), ("<strong>", "</strong>"), ("", "")] {
+			assert!(HighlightEncoder::Html.allows_tags(pre_tag, post_tag), "{}{} should be allowed", pre_tag, post_tag);
+		}
+		for (pre_tag, post_tag) in [("<b>", "</em>"), ("<b>", ""), ("<script>", "</script>"), ("<b onmouseover=x>", "</b>"), ("<B>", "</B>")] {
+			assert!(!HighlightEncoder::Html.allows_tags(pre_tag, post_tag), "{}{} should be rejected", pre_tag, post_tag);
+		}
+	}
+
+	#[test]
+	fn raw_highlights_accept_any_tags() {
+		assert!(HighlightEncoder::Raw.allows_tags("<span class=\"hit\">", "</span>"));
+		assert_eq!(HighlightEncoder::default(), HighlightEncoder::Html);
+		assert_eq!(HighlightEncoder::Html.encoder(), "html");
+		assert_eq!(HighlightEncoder::Raw.encoder(), "default");
+	}
+
+	#[test]
+	fn projections_reject_unknown_and_private_fields() {
+		assert_eq!(Projection::parse(Some("name,secret")).unwrap_err(), "Unknown field: secret");
+		for private in ["tenant_id", "owner_id", "visibility"] {
+			assert!(Projection::parse(Some(private)).is_err(), "{} should be rejected", private);
+		}
+	}
+
+	#[test]
+	fn projections_keep_the_requested_public_fields_and_the_id_and_type() {
+		let projection = Projection::parse(Some(" name, ,picture ")).unwrap();
+		let user = json!({
+			"type": "User",
+			"id": "42",
+			"name": "Ada",
+			"lastname": "Lovelace",
+			"picture": "ada.png",
+			"tenant_id": "acme"
+		});
+
+		assert_eq!(projection.apply(&user), json!({"type": "User", "id": "42", "name": "Ada", "picture": "ada.png"}));
+	}
+
+	#[test]
+	fn projections_leave_out_private_fields_even_when_returning_everything() {
+		let course = json!({
+			"type": "Course",
+			"id": "42",
+			"name": "Rust",
+			"owner_id": "7",
+			"visibility": "private",
+			"tenant_id": "acme"
+		});
+
+		assert_eq!(Projection::parse(None).unwrap().apply(&course), json!({"type": "Course", "id": "42", "name": "Rust"}));
+		for private in ["owner_id", "visibility", "tenant_id"] {
+			assert!(!Projection::default().source_includes().contains(&private));
+		}
+	}
+
+	#[test]
+	fn projections_only_keep_the_public_fields_of_the_hits_type() {
+		// `picture` is public on users and courses, not on categories.
+		let projection = Projection::parse(Some("picture")).unwrap();
+		let category = json!({"type": "Category", "id": "42", "name": "Rust", "picture": "rust.png"});
+
+		assert_eq!(projection.apply(&category), json!({"type": "Category", "id": "42"}));
+		assert_eq!(projection.apply(&json!({"id": "42"})), json!({}));
+	}
+}
@@ -0,0 +1,219 @@
+use jsonwebtoken::{decode, decode_header, Algorithm, DecodingKey, Validation};
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::domain::access::{Caller, Scope};
+
+use super::config::AuthSettings;
+
+#[derive(Debug, Deserialize)]
+struct Claims {
+	sub: Option<String>,
+	role: Option<String>,
+	tenant: Option<String>,
+	/// Space separated scopes, as in OAuth 2.0.
+	scope: Option<String>,
+}
+
+/// Validates the credentials callers present: JWTs signed with `auth.jwt_secret` (HS256) or
+/// the key in `auth.jwt_public_key_file` (RS256), and the static service tokens
+/// `auth.index_token` and `auth.admin_token`.
+pub struct Authenticator {
+	hs256_key: Option<DecodingKey>,
+	rs256_key: Option<DecodingKey>,
+	index_token: Option<String>,
+	admin_token: Option<String>,
+}
+
+impl Authenticator {
+	pub fn new(settings: &AuthSettings) -> Result<Self, String> {
+		let rs256_key = match &settings.jwt_public_key_file {
+			Some(path) => {
+				let pem = std::fs::read(path).map_err(|e| format!("Cannot read auth.jwt_public_key_file {}: {}", path, e))?;
+				Some(DecodingKey::from_rsa_pem(&pem).map_err(|e| format!("Invalid auth.jwt_public_key_file {}: {}", path, e))?)
+			}
+			None => None,
+		};
+
+		Ok(Authenticator {
+			hs256_key: settings.jwt_secret.as_ref().map(|secret| DecodingKey::from_secret(secret.expose().as_bytes())),
+			rs256_key,
+			index_token: settings.index_token.as_ref().map(|token| token.expose().to_string()),
+			admin_token: settings.admin_token.as_ref().map(|token| token.expose().to_string()),
+		})
+	}
+
+	pub fn authenticate(&self, token: &str) -> Result<Caller, String> {
+		if self.admin_token.as_deref().is_some_and(|t| constant_time_eq(t, token)) {
+			return Ok(Caller::service(vec![Scope::Admin]));
+		}
+		if self.index_token.as_deref().is_some_and(|t| constant_time_eq(t, token)) {
+			return Ok(Caller::service(vec![Scope::Index]));
+		}
+
+		let header = decode_header(token).map_err(|e| format!("Invalid token: {}", e))?;
+		let key = match header.alg {
+			Algorithm::HS256 => self.hs256_key.as_ref(),
+			Algorithm::RS256 => self.rs256_key.as_ref(),
+			_ => None,
+		};
+		let Some(key) = key else {
+			return Err(format!("Tokens signed with {:?} are not accepted", header.alg));
+		};
+
+		let claims = match decode::<Claims>(token, key, &Validation::new(header.alg)) {
+			Ok(data) => data.claims,
+			Err(e) => return Err(format!("Invalid token: {}", e)),
+		};
+
+		let scopes: Vec<Scope> = claims
+			.scope
+			.as_deref()
+			.unwrap_or_default()
+			.split_whitespace()
+			.filter_map(Scope::from_claim)
+			.collect();
+		Ok(Caller {
+			user_id: claims.sub.as_deref().and_then(|sub| Uuid::parse_str(sub).ok()),
+			admin: claims.role.as_deref() == Some("admin") || scopes.contains(&Scope::Admin),
+			tenant_id: claims.tenant,
+			scopes,
+			service: false,
+		})
+	}
+}
+
+/// Compares secrets without returning early on the first differing byte.
+fn constant_time_eq(a: &str, b: &str) -> bool {
+	a.len() == b.len() && a.bytes().zip(b.bytes()).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+	use jsonwebtoken::{encode, EncodingKey, Header};
+	use serde_json::{json, Value};
+
+	use super::*;
+	use crate::infrastructure::config::Secret;
+
+	const JWT_SECRET: &str = "jwt-secret";
+	const PUBLIC_KEY: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/jwt_public_key.pem");
+	const PRIVATE_KEY: &str = include_str!("../../tests/fixtures/jwt_private_key.pem");
+	/// 2100-01-01.
+	const LATER: u64 = 4_102_444_800;
+
+	fn authenticator(hs256: bool, rs256: bool) -> Authenticator {
+		Authenticator::new(&AuthSettings {
+			jwt_secret: hs256.then(|| Secret::new(JWT_SECRET)),
+			jwt_public_key_file: rs256.then(|| PUBLIC_KEY.to_string()),
+			index_token: Some(Secret::new("index-token")),
+			admin_token: Some(Secret::new("admin-token")),
+			..AuthSettings::default()
+		}).unwrap()
+	}
+
+	fn hs256(claims: Value) -> String {
+		encode(&Header::new(Algorithm::HS256), &claims, &EncodingKey::from_secret(JWT_SECRET.as_bytes())).unwrap()
+	}
+
+	fn rs256(claims: Value) -> String {
+		encode(&Header::new(Algorithm::RS256), &claims, &EncodingKey::from_rsa_pem(PRIVATE_KEY.as_bytes()).unwrap()).unwrap()
+	}
+
+	#[test]
+	fn tokens_are_checked_with_the_key_of_their_algorithm() {
+		let user = Uuid::new_v4();
+		let claims = json!({"sub": user, "tenant": "acme", "exp": LATER});
+		let authenticator = authenticator(true, true);
+
+		for token in [hs256(claims.clone()), rs256(claims)] {
+			let caller = authenticator.authenticate(&token).unwrap();
+			assert_eq!(caller.user_id, Some(user));
+			assert_eq!(caller.tenant(), "acme");
+			assert!(!caller.service);
+		}
+	}
+
+	#[test]
+	fn algorithms_without_a_configured_key_are_refused() {
+		let claims = json!({"sub": Uuid::new_v4(), "exp": LATER});
+
+		assert_eq!(
+			authenticator(false, true).authenticate(&hs256(claims.clone())).unwrap_err(),
+			"Tokens signed with HS256 are not accepted"
+		);
+		assert_eq!(
+			authenticator(true, false).authenticate(&rs256(claims)).unwrap_err(),
+			"Tokens signed with RS256 are not accepted"
+		);
+	}
+
+	#[test]
+	fn hs256_tokens_signed_with_the_public_key_are_refused() {
+		// The algorithm confusion attack: the public key is no secret, so it must never verify
+		// an HMAC.
+		let public_key = std::fs::read(PUBLIC_KEY).unwrap();
+		let token = encode(
+			&Header::new(Algorithm::HS256),
+			&json!({"sub": Uuid::new_v4(), "scope": "search:admin", "exp": LATER}),
+			&EncodingKey::from_secret(&public_key),
+		).unwrap();
+
+		assert!(authenticator(false, true).authenticate(&token).is_err());
+		assert!(authenticator(true, true).authenticate(&token).is_err());
+	}
+
+	#[test]
+	fn expired_and_tampered_tokens_are_refused() {
+		let authenticator = authenticator(true, false);
+		let expired = hs256(json!({"sub": Uuid::new_v4(), "exp": 1_000_000_000}));
+		assert!(authenticator.authenticate(&expired).unwrap_err().contains("ExpiredSignature"));
+
+		let token = hs256(json!({"sub": Uuid::new_v4(), "exp": LATER}));
+		let (unsigned, _) = token.rsplit_once('.').unwrap();
+		assert!(authenticator.authenticate(&format!("{}.{}", unsigned, "AAAA")).is_err());
+		assert!(authenticator.authenticate("not a token").is_err());
+	}
+
+	#[test]
+	fn scopes_are_read_from_the_space_separated_claim() {
+		let token = hs256(json!({"sub": Uuid::new_v4(), "scope": "openid search:index  other", "exp": LATER}));
+		let caller = authenticator(true, false).authenticate(&token).unwrap();
+		assert_eq!(caller.scopes, vec![Scope::Index]);
+		assert!(!caller.admin);
+
+		let token = hs256(json!({"sub": Uuid::new_v4(), "scope": "search:index search:admin", "exp": LATER}));
+		let caller = authenticator(true, false).authenticate(&token).unwrap();
+		assert_eq!(caller.scopes, vec![Scope::Index, Scope::Admin]);
+		assert!(caller.admin);
+
+		let token = hs256(json!({"sub": Uuid::new_v4(), "role": "admin", "exp": LATER}));
+		let caller = authenticator(true, false).authenticate(&token).unwrap();
+		assert!(caller.admin);
+		assert!(caller.scopes.is_empty());
+	}
+
+	#[test]
+	fn service_tokens_are_recognized_before_tokens_are_decoded() {
+		let authenticator = authenticator(true, false);
+
+		let caller = authenticator.authenticate("admin-token").unwrap();
+		assert!(caller.service && caller.admin);
+		assert_eq!(caller.scopes, vec![Scope::Admin]);
+
+		let caller = authenticator.authenticate("index-token").unwrap();
+		assert!(caller.service && !caller.admin);
+		assert_eq!(caller.scopes, vec![Scope::Index]);
+
+		// Even a valid JWT is a service token when it is configured as one.
+		let jwt = hs256(json!({"sub": Uuid::new_v4(), "tenant": "acme", "exp": LATER}));
+		let authenticator = Authenticator::new(&AuthSettings {
+			jwt_secret: Some(Secret::new(JWT_SECRET)),
+			index_token: Some(Secret::new(&jwt)),
+			..AuthSettings::default()
+		}).unwrap();
+		let caller = authenticator.authenticate(&jwt).unwrap();
+		assert!(caller.service);
+		assert_eq!(caller.tenant_id, None);
+	}
+}
@@ -1,76 +1,275 @@
 
 #[cfg(any(feature = "native-tls", feature = "rustls-tls"))]
-use elasticsearch::cert::CertificateValidation;
+use elasticsearch::http::headers::{HeaderMap, HeaderName, HeaderValue, AUTHORIZATION};
 use elasticsearch::{
     auth::Credentials,
-    http::transport::{SingleNodeConnectionPool, TransportBuilder},
-    Elasticsearch, Error, DEFAULT_ADDRESS,
+    http::transport::{CloudConnectionPool, CloudId, SingleNodeConnectionPool, TransportBuilder},
+    Elasticsearch, Error,
 };
-use sysinfo::SystemExt;
+use std::time::{Duration, Instant};
+
+use serde_json::{json, Value};
+use tokio::sync::Mutex;
 use url::Url;
 
-pub fn create_client() -> Result<Elasticsearch, Error> {
-	fn cluster_addr() -> String {
-        match std::env::var("ELASTICSEARCH_URL") {
-            Ok(server) => server,
-            Err(_) => DEFAULT_ADDRESS.into(),
+#[cfg(any(feature = "native-tls", feature = "rustls-tls"))]
+use super::tls;
+use super::{config::ElasticsearchSettings, pool::MultiNodeConnectionPool};
+
+/// Builds a client for the cluster, and the pool behind it when it spreads requests over
+/// several nodes.
+pub fn create_client(settings: &ElasticsearchSettings) -> Result<(Elasticsearch, Option<MultiNodeConnectionPool>), Error> {
+    // Settings are validated at startup, so the URLs parse.
+    let mut urls: Vec<Url> = settings.urls.iter().map(|url| Url::parse(url).unwrap()).collect();
+
+    // credentials in the urls are moved out of them; explicitly configured ones take precedence
+    let mut url_credentials = None;
+    for url in urls.iter_mut() {
+        if !url.username().is_empty() {
+            let username = url.username().to_string();
+            let password = url.password().unwrap_or_default().to_string();
+            url.set_username("").unwrap();
+            url.set_password(None).unwrap();
+            url_credentials.get_or_insert(Credentials::Basic(username, password));
         }
     }
+    #[allow(unused_mut)]
+    let mut credentials = match settings {
+        ElasticsearchSettings { api_key_id: Some(id), api_key: Some(key), .. } => {
+            Some(Credentials::ApiKey(id.clone(), key.expose().to_string()))
+        }
+        ElasticsearchSettings { bearer_token: Some(token), .. } => Some(Credentials::Bearer(token.expose().to_string())),
+        ElasticsearchSettings { username: Some(username), password: Some(password), .. } => {
+            Some(Credentials::Basic(username.clone(), password.expose().to_string()))
+        }
+        _ => url_credentials,
+    };
 
-    /// Determines if Fiddler.exe proxy process is running
-    fn running_proxy() -> bool {
-        let system = sysinfo::System::new();
-        !system.get_process_by_name("Fiddler").is_empty()
-    }
+    // Elastic Cloud routes to healthy nodes itself; a single node without sniffing has nothing
+    // to fail over to.
+    #[cfg(any(feature = "native-tls", feature = "rustls-tls"))]
+    let cert_validation = tls::certificate_validation(&settings.tls)?;
+    #[cfg(any(feature = "native-tls", feature = "rustls-tls"))]
+    let client_certificate = tls::client_certificate(&settings.tls)?;
 
-    let mut url = Url::parse(cluster_addr().as_ref()).unwrap();
+    let hosts: Vec<String> = match &settings.cloud_id {
+        Some(cloud_id) => vec![CloudId::parse(cloud_id)?.url.host_str().unwrap_or_default().to_string()],
+        None => urls.iter().map(|url| url.host_str().unwrap_or_default().to_string()).collect(),
+    };
+    let hosts: Vec<&str> = hosts.iter().map(String::as_str).collect();
+    let proxy = match settings.proxy.url_for(&hosts) {
+        Some(url) => Some(Url::parse(url)?),
+        None => None,
+    };
 
-    // if the url is https and specifies a username and password, remove from the url and set credentials
-    let credentials = if url.scheme() == "https" {
-        let username = if !url.username().is_empty() {
-            let u = url.username().to_string();
-            url.set_username("").unwrap();
-            u
-        } else {
-            std::env::var("ES_USERNAME").unwrap_or_else(|_| "elastic".into())
-        };
-
-        let password = match url.password() {
-            Some(p) => {
-                let pass = p.to_string();
-                url.set_password(None).unwrap();
-                pass
+    // Pins are validated to come without a proxy, so the hosts above are the nodes' own.
+    #[cfg(any(feature = "native-tls", feature = "rustls-tls"))]
+    let tunnel_secret = tls::pin_nodes(&settings.tls, &mut urls)?;
+    #[cfg(not(any(feature = "native-tls", feature = "rustls-tls")))]
+    let tunnel_secret: Option<String> = None;
+
+    let mut pool = None;
+    let mut builder = match &settings.cloud_id {
+        Some(cloud_id) => TransportBuilder::new(CloudConnectionPool::new(cloud_id)?),
+        None if urls.len() == 1 && settings.sniff_interval_secs.is_none() => {
+            TransportBuilder::new(SingleNodeConnectionPool::new(urls.remove(0)))
+        }
+        None => {
+            let multi_node = MultiNodeConnectionPool::new(urls);
+            pool = Some(multi_node.clone());
+            TransportBuilder::new(multi_node)
+        }
+    };
+    builder = builder.timeout(Duration::from_secs(settings.timeout_secs));
+
+    #[cfg(any(feature = "native-tls", feature = "rustls-tls"))]
+    {
+        builder = builder.cert_validation(cert_validation);
+        if let Some(secret) = &tunnel_secret {
+            builder = builder.header(HeaderName::from_static(tls::TUNNEL_HEADER), tunnel_header(secret));
+        }
+        // The transport takes a single set of credentials, so a client certificate takes that
+        // slot and any other credentials go in a default header instead.
+        if let Some(certificate) = client_certificate {
+            builder = builder.auth(Credentials::Certificate(certificate));
+            if let Some(c) = credentials.take() {
+                builder = builder.header(AUTHORIZATION, authorization(&c));
             }
-            None => std::env::var("ES_PASSWORD").unwrap_or_else(|_| "password".into()),
-        };
+        }
+    }
+
+    if let Some(c) = credentials {
+        builder = builder.auth(c);
+    }
 
-        Some(Credentials::Basic(username, password))
-    } else {
-        None
+    builder = match &proxy {
+        Some(url) => builder.proxy(
+            url.clone(),
+            settings.proxy.username.as_deref(),
+            settings.proxy.password.as_ref().map(|password| password.expose()),
+        ),
+        None => builder.disable_proxy(),
     };
 
-    let conn_pool = SingleNodeConnectionPool::new(url);
-    let mut builder = TransportBuilder::new(conn_pool);
+    let transport = builder.build()?;
+    let client = Elasticsearch::new(transport);
+    if let Some(pool) = &pool {
+        pool.monitor(
+            client.clone(),
+            health_check_client(settings, proxy, tunnel_secret.as_deref())?,
+            Duration::from_secs(settings.health_check_interval_secs),
+            settings.sniff_interval_secs.map(Duration::from_secs),
+        );
+    }
+    Ok((client, pool))
+}
+
+/// The client is built on first use rather than at startup, so the service starts while the
+/// cluster is unreachable or misconfigured. After a failed build, further attempts wait an
+/// exponentially growing delay instead of retrying on every request.
+pub struct LazyClient {
+    settings: ElasticsearchSettings,
+    state: Mutex<ClientState>,
+}
+
+#[derive(Default)]
+struct ClientState {
+    client: Option<(Elasticsearch, Option<MultiNodeConnectionPool>)>,
+    last_error: Option<String>,
+    failures: u32,
+    retry_at: Option<Instant>,
+}
+
+impl LazyClient {
+    const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+    pub fn new(settings: ElasticsearchSettings) -> Self {
+        LazyClient {
+            settings,
+            state: Mutex::new(ClientState::default()),
+        }
+    }
+
+    pub async fn get(&self) -> Result<Elasticsearch, String> {
+        let mut state = self.state.lock().await;
+        if let Some((client, _)) = &state.client {
+            return Ok(client.clone());
+        }
+        if let Some(retry_at) = state.retry_at.filter(|retry_at| *retry_at > Instant::now()) {
+            return Err(format!(
+                "Elasticsearch client unavailable, retrying in {}s: {}",
+                (retry_at - Instant::now()).as_secs() + 1,
+                state.last_error.as_deref().unwrap_or_default()
+            ));
+        }
 
-    builder = match credentials {
-        Some(c) => {
-            builder = builder.auth(c);
+        // Building reads certificate files and starts the tunnels to pinned nodes.
+        let settings = self.settings.clone();
+        let built = tokio::task::spawn_blocking(move || create_client(&settings))
+            .await
+            .map_err(|e| e.to_string())
+            .and_then(|built| built.map_err(|e| e.to_string()));
+        match built {
+            Ok((client, pool)) => {
+                if state.failures > 0 {
+                    println!("Elasticsearch client built after {} failed attempts", state.failures);
+                }
+                state.client = Some((client.clone(), pool));
+                state.last_error = None;
+                state.failures = 0;
+                state.retry_at = None;
+                Ok(client)
+            }
+            Err(e) => {
+                state.failures += 1;
+                let backoff = Duration::from_secs(1 << state.failures.min(6)).min(Self::MAX_BACKOFF);
+                println!("Failed to build Elasticsearch client (attempt {}): {}", state.failures, e);
+                state.retry_at = Some(Instant::now() + backoff);
+                state.last_error = Some(e.clone());
+                Err(e)
+            }
+        }
+    }
 
-            #[cfg(any(feature = "native-tls", feature = "rustls-tls"))]
-            {
-                builder = builder.cert_validation(CertificateValidation::None);
+    /// Whether the client is built, with the health of each node when there are several.
+    pub async fn status(&self) -> (bool, Value) {
+        let state = self.state.lock().await;
+        match &state.client {
+            Some((_, pool)) => {
+                let nodes: Vec<Value> = pool
+                    .iter()
+                    .flat_map(|pool| pool.status())
+                    .map(|(url, up)| json!({"url": url.as_str(), "up": up}))
+                    .collect();
+                let up = nodes.is_empty() || nodes.iter().any(|node| node["up"] == true);
+                (up, json!({"client": "built", "nodes": nodes}))
             }
+            None if state.failures == 0 => (true, json!({"client": "not built yet"})),
+            None => (false, json!({
+                "client": "failed",
+                "error": state.last_error,
+                "failures": state.failures,
+                "retry_in_secs": state.retry_at.map(|retry_at| retry_at.saturating_duration_since(Instant::now()).as_secs())
+            })),
+        }
+    }
+}
 
-            builder
+/// Client probing nodes for the pool's health checks: it only needs to reach them, so it
+/// skips credentials, but validates certificates like the client and goes through the same
+/// proxy and tunnels.
+#[allow(unused_variables)]
+fn health_check_client(settings: &ElasticsearchSettings, proxy: Option<Url>, tunnel_secret: Option<&str>) -> Result<reqwest::Client, Error> {
+    let interval = Duration::from_secs(settings.health_check_interval_secs);
+    #[allow(unused_mut)]
+    let mut builder = reqwest::Client::builder().timeout(interval.min(Duration::from_secs(5)));
+    #[cfg(any(feature = "native-tls", feature = "rustls-tls"))]
+    {
+        let tls = &settings.tls;
+        if tls.accept_invalid_certs {
+            builder = builder.danger_accept_invalid_certs(true);
+        } else if let Some(path) = &tls.ca_file {
+            let pem = std::fs::read(path)?;
+            builder = builder.add_root_certificate(reqwest::Certificate::from_pem(&pem)?);
+        }
+        if let Some(secret) = tunnel_secret {
+            let mut headers = HeaderMap::new();
+            headers.insert(HeaderName::from_static(tls::TUNNEL_HEADER), tunnel_header(secret));
+            builder = builder.default_headers(headers);
+        }
+    }
+    let builder = match proxy {
+        Some(url) => {
+            let mut proxy = reqwest::Proxy::all(url)?;
+            if let Some(username) = &settings.proxy.username {
+                let password = settings.proxy.password.as_ref().map(|password| password.expose()).unwrap_or_default();
+                proxy = proxy.basic_auth(username, password);
+            }
+            builder.proxy(proxy)
         }
-        None => builder,
+        None => builder.no_proxy(),
     };
+    Ok(builder.build()?)
+}
 
-    if running_proxy() {
-        let proxy_url = Url::parse("http://localhost:8888").unwrap();
-        builder = builder.proxy(proxy_url, None, None);
-    }
+/// Value of the tunnel header, marked sensitive so it is never logged.
+#[cfg(any(feature = "native-tls", feature = "rustls-tls"))]
+fn tunnel_header(secret: &str) -> HeaderValue {
+    let mut header = HeaderValue::from_str(secret).unwrap_or_else(|_| HeaderValue::from_static(""));
+    header.set_sensitive(true);
+    header
+}
 
-    let transport = builder.build()?;
-    Ok(Elasticsearch::new(transport))
-}
\ No newline at end of file
+/// `Authorization` header carrying `credentials`, marked sensitive so it is never logged.
+#[cfg(any(feature = "native-tls", feature = "rustls-tls"))]
+fn authorization(credentials: &Credentials) -> HeaderValue {
+    let value = match credentials {
+        Credentials::Basic(username, password) => format!("Basic {}", base64::encode(&format!("{}:{}", username, password))),
+        Credentials::Bearer(token) => format!("Bearer {}", token),
+        Credentials::ApiKey(id, key) => format!("ApiKey {}", base64::encode(&format!("{}:{}", id, key))),
+        Credentials::Certificate(_) => String::new(),
+    };
+    let mut header = HeaderValue::from_str(&value).unwrap_or_else(|_| HeaderValue::from_static(""));
+    header.set_sensitive(true);
+    header
+}
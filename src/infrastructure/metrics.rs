@@ -0,0 +1,76 @@
+use prometheus::{Encoder, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, Opts, Registry, TextEncoder};
+
+/// Metrics exposed at `/metrics` in Prometheus' text format.
+pub struct Metrics {
+    registry: Registry,
+    /// By method, route and response status.
+    pub http_requests: IntCounterVec,
+    /// By method and route.
+    pub http_request_duration: HistogramVec,
+    /// Each attempt at a request to the cluster, by operation and outcome: the status code, or
+    /// `timeout`, `connect_error` or `error` when there was no response.
+    pub elasticsearch_request_duration: HistogramVec,
+    /// By operation.
+    pub elasticsearch_retries: IntCounterVec,
+    /// Bulk items the cluster rejected, by item status.
+    pub bulk_item_failures: IntCounterVec,
+    pub zero_result_searches: IntCounter,
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        let metrics = Metrics {
+            registry: Registry::new(),
+            http_requests: IntCounterVec::new(
+                Opts::new("http_requests_total", "HTTP requests handled"),
+                &["method", "route", "status"],
+            )
+            .unwrap(),
+            http_request_duration: HistogramVec::new(
+                HistogramOpts::new("http_request_duration_seconds", "Time spent handling HTTP requests"),
+                &["method", "route"],
+            )
+            .unwrap(),
+            elasticsearch_request_duration: HistogramVec::new(
+                HistogramOpts::new("elasticsearch_request_duration_seconds", "Time spent on each attempt at an Elasticsearch request"),
+                &["operation", "outcome"],
+            )
+            .unwrap(),
+            elasticsearch_retries: IntCounterVec::new(
+                Opts::new("elasticsearch_retries_total", "Elasticsearch requests and bulk items sent again"),
+                &["operation"],
+            )
+            .unwrap(),
+            bulk_item_failures: IntCounterVec::new(
+                Opts::new("elasticsearch_bulk_item_failures_total", "Bulk items Elasticsearch failed to index"),
+                &["status"],
+            )
+            .unwrap(),
+            zero_result_searches: IntCounter::new("search_zero_results_total", "Searches that matched no post").unwrap(),
+        };
+
+        // Registering only fails on duplicate names, which the names above are not.
+        let registry = &metrics.registry;
+        registry.register(Box::new(metrics.http_requests.clone())).unwrap();
+        registry.register(Box::new(metrics.http_request_duration.clone())).unwrap();
+        registry.register(Box::new(metrics.elasticsearch_request_duration.clone())).unwrap();
+        registry.register(Box::new(metrics.elasticsearch_retries.clone())).unwrap();
+        registry.register(Box::new(metrics.bulk_item_failures.clone())).unwrap();
+        registry.register(Box::new(metrics.zero_result_searches.clone())).unwrap();
+        metrics
+    }
+
+    /// Every metric in Prometheus' text exposition format, with its content type.
+    pub fn render(&self) -> (String, String) {
+        let encoder = TextEncoder::new();
+        let mut buffer = Vec::new();
+        encoder.encode(&self.registry.gather(), &mut buffer).unwrap();
+        (encoder.format_type().to_string(), String::from_utf8(buffer).unwrap_or_default())
+    }
+}
@@ -1,3 +1,10 @@
 // This file is intentionally left blank.
+pub mod auth;
 pub mod client;
-pub mod repositories;
\ No newline at end of file
+pub mod config;
+pub mod metrics;
+pub mod pool;
+pub mod repositories;
+pub mod resilience;
+#[cfg(any(feature = "native-tls", feature = "rustls-tls"))]
+pub mod tls;
\ No newline at end of file
@@ -0,0 +1,198 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc, Mutex, RwLock,
+    },
+    time::Duration,
+};
+
+use elasticsearch::{
+    http::transport::{Connection, ConnectionPool},
+    nodes::NodesInfoParts,
+    Elasticsearch,
+};
+use serde_json::Value;
+use url::Url;
+
+/// A node of the cluster and whether its last health check succeeded.
+#[derive(Debug)]
+struct Node {
+    url: Url,
+    connection: Connection,
+    alive: AtomicBool,
+}
+
+#[derive(Debug, Default)]
+struct PoolState {
+    nodes: RwLock<Vec<&'static Node>>,
+    /// Every node ever seen, by URL.
+    ///
+    /// `ConnectionPool::next` hands out references that must outlive any lock on `nodes`, so
+    /// nodes are leaked once per distinct URL and reused when they rejoin the cluster.
+    known: Mutex<HashMap<Url, &'static Node>>,
+    cursor: AtomicUsize,
+}
+
+/// Round-robins requests over the nodes that passed their last health check. When none did,
+/// it keeps cycling through all of them so requests still reach a node that came back.
+///
+/// Health checks and sniffing run in the background (see `monitor`); a failed request does not
+/// mark its node down by itself.
+#[derive(Debug, Clone)]
+pub struct MultiNodeConnectionPool {
+    state: Arc<PoolState>,
+}
+
+impl MultiNodeConnectionPool {
+    pub fn new(urls: Vec<Url>) -> Self {
+        let pool = MultiNodeConnectionPool {
+            state: Arc::new(PoolState::default()),
+        };
+        pool.set_nodes(urls);
+        pool
+    }
+
+    /// Replaces the node list. Nodes already known keep their health.
+    fn set_nodes(&self, urls: Vec<Url>) {
+        let mut known = self.state.known.lock().unwrap();
+        let nodes = urls
+            .into_iter()
+            .map(|url| {
+                *known.entry(url.clone()).or_insert_with(|| {
+                    Box::leak(Box::new(Node {
+                        connection: Connection::new(url.clone()),
+                        url,
+                        alive: AtomicBool::new(true),
+                    }))
+                })
+            })
+            .collect();
+        *self.state.nodes.write().unwrap() = nodes;
+    }
+
+    fn nodes(&self) -> Vec<&'static Node> {
+        self.state.nodes.read().unwrap().clone()
+    }
+
+    /// URLs of the nodes in the pool and whether each passed its last health check.
+    pub fn status(&self) -> Vec<(Url, bool)> {
+        self.nodes()
+            .iter()
+            .map(|node| (node.url.clone(), node.alive.load(Ordering::Relaxed)))
+            .collect()
+    }
+
+    /// Health checks every node each `interval` with `http` and, when `sniff_interval` is set, refreshes
+    /// the node list from the cluster's own view of its HTTP addresses.
+    pub fn monitor(&self, client: Elasticsearch, http: reqwest::Client, interval: Duration, sniff_interval: Option<Duration>) {
+        let pool = self.clone();
+        tokio::spawn(async move {
+            let mut last_sniff = tokio::time::Instant::now();
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                if sniff_interval.is_some_and(|every| last_sniff.elapsed() >= every) {
+                    last_sniff = tokio::time::Instant::now();
+                    pool.sniff(&client).await;
+                }
+                pool.check(&http).await;
+            }
+        });
+    }
+
+    /// A node is up when it answers HTTP at all; an authentication error still proves it is
+    /// serving requests.
+    async fn check(&self, http: &reqwest::Client) {
+        for node in self.nodes() {
+            let alive = http.get(node.url.clone()).send().await.is_ok();
+            if node.alive.swap(alive, Ordering::Relaxed) != alive {
+                println!("Elasticsearch node {} is {}", node.url, if alive { "up" } else { "down" });
+            }
+        }
+    }
+
+    async fn sniff(&self, client: &Elasticsearch) {
+        let response = match client.nodes().info(NodesInfoParts::Metric(&["http"])).send().await {
+            Ok(response) => response,
+            Err(e) => {
+                println!("Failed to sniff nodes: {}", e);
+                return;
+            }
+        };
+        let json: Value = match response.json().await {
+            Ok(json) => json,
+            Err(e) => {
+                println!("Failed to sniff nodes: {}", e);
+                return;
+            }
+        };
+
+        let scheme = self.nodes().first().map(|node| node.url.scheme().to_string()).unwrap_or("http".into());
+        let urls: Vec<Url> = json["nodes"]
+            .as_object()
+            .map(|nodes| nodes
+                .values()
+                .filter_map(|node| node["http"]["publish_address"].as_str())
+                .filter_map(|address| publish_url(&scheme, address))
+                .collect())
+            .unwrap_or_default();
+
+        if !urls.is_empty() {
+            self.set_nodes(urls);
+        }
+    }
+}
+
+/// Publish addresses look like `10.0.0.1:9200`, or `host/10.0.0.1:9200` when the node has a
+/// hostname, which is then preferred.
+fn publish_url(scheme: &str, address: &str) -> Option<Url> {
+    let address = match address.split_once('/') {
+        Some((host, ip_port)) if !host.is_empty() => {
+            let port = ip_port.rsplit_once(':')?.1;
+            format!("{}:{}", host, port)
+        }
+        Some((_, ip_port)) => ip_port.to_string(),
+        None => address.to_string(),
+    };
+    Url::parse(&format!("{}://{}", scheme, address)).ok()
+}
+
+impl ConnectionPool for MultiNodeConnectionPool {
+    fn next(&self) -> &Connection {
+        let nodes = self.state.nodes.read().unwrap();
+        let alive: Vec<&'static Node> = nodes.iter().copied().filter(|node| node.alive.load(Ordering::Relaxed)).collect();
+        let candidates = if alive.is_empty() { nodes.as_slice() } else { alive.as_slice() };
+        let cursor = self.state.cursor.fetch_add(1, Ordering::Relaxed);
+        let node: &'static Node = candidates[cursor % candidates.len()];
+        &node.connection
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn publish_urls_prefer_the_hostname() {
+        let url = |address| publish_url("https", address).map(String::from);
+
+        assert_eq!(url("es-1.internal/10.0.0.1:9200").as_deref(), Some("https://es-1.internal:9200/"));
+        assert_eq!(url("es-1.internal/[::1]:9200").as_deref(), Some("https://es-1.internal:9200/"));
+    }
+
+    #[test]
+    fn publish_urls_fall_back_to_the_ip() {
+        let url = |address| publish_url("http", address).map(String::from);
+
+        assert_eq!(url("10.0.0.1:9200").as_deref(), Some("http://10.0.0.1:9200/"));
+        assert_eq!(url("/10.0.0.1:9200").as_deref(), Some("http://10.0.0.1:9200/"));
+        assert_eq!(url("[::1]:9200").as_deref(), Some("http://[::1]:9200/"));
+    }
+
+    #[test]
+    fn unusable_publish_addresses_are_skipped() {
+        assert_eq!(publish_url("http", "es-1.internal/10.0.0.1"), None);
+        assert_eq!(publish_url("http", "not a host:9200"), None);
+    }
+}
@@ -0,0 +1,177 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+use rocket::http::Status;
+use rocket::response::status::Custom;
+use rocket::serde::json::{json, Json};
+use serde_json::Value;
+use uuid::Uuid;
+
+use crate::domain::{
+	access::Access,
+	health::HealthCheck,
+	post::Post,
+	post_repository::{Migration, PostRepository, PostResult, SearchResult},
+	search::Projection,
+};
+
+/// Posts kept in memory for use case tests. Like the cluster behind `PostRepositoryImpl`, it
+/// stamps indexed posts with their tenant and only lets each operation see its tenant's posts.
+/// Queries are not evaluated: a search returns every post of the tenant.
+#[derive(Default)]
+pub struct InMemoryPostRepository {
+	/// Indexed documents by id, as the cluster stores them.
+	pub documents: Mutex<HashMap<Uuid, Value>>,
+	/// Bodies of the searches run.
+	pub searches: Mutex<Vec<Value>>,
+	/// Bodies of the update-by-query requests started, with their tenant.
+	pub updates_by_query: Mutex<Vec<(Value, String)>>,
+	/// Whether a migration holds writes back.
+	pub migrating: AtomicBool,
+	/// Fields the index lacks, as in an index created before they were added.
+	pub unmapped_fields: Mutex<Vec<String>>,
+}
+
+impl InMemoryPostRepository {
+	/// Stores `post` as `tenant`'s, as indexing it would.
+	pub fn insert(&self, post: &Post, tenant: &str) {
+		let mut document = json!(post);
+		document["tenant_id"] = json!(tenant);
+		self.documents.lock().unwrap().insert(post.id(), document);
+	}
+
+	/// The stored document with `id`, whatever its tenant.
+	pub fn document(&self, id: &Uuid) -> Option<Value> {
+		self.documents.lock().unwrap().get(id).cloned()
+	}
+
+	fn tenant_documents(&self, tenant: &str) -> Vec<Value> {
+		self.documents
+			.lock()
+			.unwrap()
+			.values()
+			.filter(|document| belongs_to(document, tenant))
+			.cloned()
+			.collect()
+	}
+}
+
+/// Whether `document` is `tenant`'s, by the same rule as `Access::tenant_filter`.
+fn belongs_to(document: &Value, tenant: &str) -> bool {
+	serde_json::from_value::<Access>(document.clone()).is_ok_and(|access| access.tenant() == tenant)
+}
+
+impl PostRepository for InMemoryPostRepository {
+	async fn search(&self, query: sea_orm::prelude::Json, projection: &Projection, tenant: &str) -> Result<SearchResult, Custom<sea_orm::prelude::Json>> {
+		self.searches.lock().unwrap().push(query);
+		Ok(SearchResult {
+			posts: self
+				.tenant_documents(tenant)
+				.iter()
+				.map(|document| PostResult { post: projection.apply(document), highlight: json!({}), explanation: None })
+				.collect(),
+			aggregations: json!({}),
+		})
+	}
+
+	async fn set_refresh_interval(&self, _interval: sea_orm::prelude::Json) -> Result<(), Custom<sea_orm::prelude::Json>> {
+		Ok(())
+	}
+
+	async fn check_if_exists(&self, post_id: &Uuid, tenant: &str) -> Result<bool, Custom<sea_orm::prelude::Json>> {
+		Ok(self.document(post_id).is_some_and(|document| belongs_to(&document, tenant)))
+	}
+
+	async fn create_index_if_not_exists(&self, _delete: bool) -> Result<(), Custom<sea_orm::prelude::Json>> {
+		Ok(())
+	}
+
+	async fn index(&self, posts: &[Post], tenant: &str) -> Result<String, Custom<sea_orm::prelude::Json>> {
+		// Posts are created, so an id taken in any tenant conflicts.
+		if let Some(taken) = posts.iter().find(|post| self.document(&post.id()).is_some()) {
+			return Err(Custom(Status::Conflict, json!({
+				"success": false,
+				"message": format!("Post {} already exists", taken.id())
+			})));
+		}
+		for post in posts {
+			self.insert(post, tenant);
+		}
+		Ok("Post indexed successfully".to_string())
+	}
+
+	// Like the by-query writes on the tenant's alias, posts of other tenants are left alone
+	// without an error.
+	async fn update(&self, post_id: &Uuid, post: Json<Post>, tenant: &str) -> Result<Custom<sea_orm::prelude::Json>, Custom<sea_orm::prelude::Json>> {
+		if self.check_if_exists(post_id, tenant).await? {
+			self.insert(&post, tenant);
+		}
+		Ok(Custom(Status::Ok, json!({"success": true, "message": format!("Post {} updated successfully", post_id)})))
+	}
+
+	async fn delete(&self, post_id: &Uuid, tenant: &str) -> Result<Custom<sea_orm::prelude::Json>, Custom<sea_orm::prelude::Json>> {
+		if self.check_if_exists(post_id, tenant).await? {
+			self.documents.lock().unwrap().remove(post_id);
+		}
+		Ok(Custom(Status::Ok, json!({"success": true, "message": format!("Post {} deleted successfully", post_id)})))
+	}
+
+	async fn get_posts(&self, ids: &[Uuid], tenant: &str) -> Result<Vec<Post>, Custom<sea_orm::prelude::Json>> {
+		Ok(ids
+			.iter()
+			.filter_map(|id| self.document(id))
+			.filter(|document| belongs_to(document, tenant))
+			.filter_map(|document| serde_json::from_value(document).ok())
+			.collect())
+	}
+
+	async fn start_update_by_query(&self, body: sea_orm::prelude::Json, tenant: &str) -> Result<String, Custom<sea_orm::prelude::Json>> {
+		let mut updates = self.updates_by_query.lock().unwrap();
+		updates.push((body, tenant.to_string()));
+		Ok(format!("memory:{}", updates.len()))
+	}
+
+	async fn get_task(&self, task_id: &str, _tenant: Option<&str>) -> Result<Custom<sea_orm::prelude::Json>, Custom<sea_orm::prelude::Json>> {
+		Err(Custom(Status::NotFound, json!({"success": false, "message": format!("Task {} not found", task_id)})))
+	}
+
+	async fn maps_field(&self, field: &str) -> Result<bool, Custom<sea_orm::prelude::Json>> {
+		Ok(!self.unmapped_fields.lock().unwrap().iter().any(|unmapped| unmapped == field))
+	}
+
+	async fn check_writable(&self) -> Result<(), Custom<sea_orm::prelude::Json>> {
+		if self.migrating.load(Ordering::SeqCst) {
+			return Err(Custom(Status::ServiceUnavailable, json!({"success": false, "message": "Posts are being migrated"})));
+		}
+		Ok(())
+	}
+
+	async fn start_reindex(&self) -> Result<Migration, Custom<sea_orm::prelude::Json>> {
+		Err(Custom(Status::NotImplemented, json!({"success": false, "message": "Posts in memory are not reindexed"})))
+	}
+
+	async fn switch_index(&self, _migration: &Migration) -> Result<(), Custom<sea_orm::prelude::Json>> {
+		Err(Custom(Status::NotImplemented, json!({"success": false, "message": "Posts in memory are not reindexed"})))
+	}
+
+	async fn abort_reindex(&self, _migration: &Migration) -> Result<(), Custom<sea_orm::prelude::Json>> {
+		Err(Custom(Status::NotImplemented, json!({"success": false, "message": "Posts in memory are not reindexed"})))
+	}
+
+	async fn get_synonyms(&self) -> Result<Vec<String>, Custom<sea_orm::prelude::Json>> {
+		Ok(Vec::new())
+	}
+
+	async fn put_synonyms(&self, _rules: &[String]) -> Result<Custom<sea_orm::prelude::Json>, Custom<sea_orm::prelude::Json>> {
+		Ok(Custom(Status::Ok, json!({"success": true})))
+	}
+
+	async fn client_health(&self) -> HealthCheck {
+		HealthCheck::new("elasticsearch_client", true, json!({"client": "in memory"}))
+	}
+
+	async fn cluster_health(&self) -> Vec<HealthCheck> {
+		Vec::new()
+	}
+}
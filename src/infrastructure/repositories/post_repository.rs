@@ -1,6 +1,11 @@
-use elasticsearch::indices::{IndicesCreateParts, IndicesDeleteParts, IndicesExistsParts, IndicesPutSettingsParts};
-use elasticsearch::{DeleteByQueryParts, SearchParts, UpdateByQueryParts};
-use elasticsearch::{BulkOperation, BulkParts, http::StatusCode, ExistsParts};
+use std::{future::Future, sync::atomic::Ordering};
+
+use elasticsearch::cluster::ClusterHealthParts;
+use elasticsearch::http::{headers::{HeaderMap, HeaderName, HeaderValue}, request::JsonBody, response::Response, Method};
+use elasticsearch::params::Conflicts;
+use elasticsearch::indices::{IndicesCreateParts, IndicesDeleteParts, IndicesExistsParts, IndicesGetAliasParts, IndicesGetFieldMappingParts, IndicesGetParts, IndicesGetSettingsParts, IndicesPutAliasParts, IndicesPutSettingsParts, IndicesRefreshParts, IndicesReloadSearchAnalyzersParts};
+use elasticsearch::{DeleteByQueryParts, Elasticsearch, GetParts, MgetParts, SearchParts, UpdateByQueryParts};
+use elasticsearch::{BulkOperation, BulkParts, http::StatusCode};
 use rocket::response::status::Custom;
 use rocket::{
     http::Status,
@@ -9,76 +14,68 @@ use rocket::{
 use serde_json::Value;
 use uuid::Uuid;
 
+use crate::infrastructure::resilience::Operation;
 use crate::domain::{
+    access::Access,
+    health::HealthCheck,
     post::Post,
-    post_repository::{PostRepository, PostRepositoryImpl, PostResult},
+    post_repository::{Migration, PostRepository, PostRepositoryImpl, PostResult, SearchResult},
+    search::Projection,
 };
 
 
-impl PostRepository for PostRepositoryImpl <'_> {
+impl PostRepository for PostRepositoryImpl {
     async fn index(
         &self,
         posts: &[Post],
+        tenant: &str,
     ) -> Result<String, Custom<sea_orm::prelude::Json>> {
         // Implement the logic to index a post
-		let client = match &self.client {
-			Ok(client) => client,
-			Err(e) => return Err(Custom(Status::InternalServerError, json!({"success": false, "message": e.to_string()})))
-		};
-		let body: Vec<BulkOperation<_>> = posts
+		let client = self.client().await?;
+		let docs: Vec<(String, Value)> = posts
 			.iter()
 			.map(|p| {
-				let id = p.id().to_string();
-				BulkOperation::index(p).id(&id).routing(&id).into()
+				let mut doc = json!(p);
+				doc["tenant_id"] = json!(tenant);
+				(p.id().to_string(), doc)
 			})
 			.collect();
-
 		for post in posts {
-			if self.check_if_exists(&post.id()).await {
+			if self.check_if_exists(&post.id(), tenant).await? {
 				println!("Post already exists {}", post.id());
 				return Ok("Error: Post already exists".to_string());
 			}
 		}
 
-		let response = client
-			.bulk(BulkParts::Index(self.index))
-			.body(body)
-			.send()
-			.await
-			.unwrap();
-
-		let json: Value = match response.json().await {
-			Ok(json) => json,
-			Err(e) => {
-				return Err(Custom(Status::InternalServerError, json!({"success": false, "message": e.to_string()})));
-			}
-		};
-
-		if json["errors"].as_bool().unwrap() {
-			let failed: Vec<&Value> = json["items"]
-				.as_array()
-				.unwrap()
-				.iter()
-				.filter(|v| !v["error"].is_null())
-				.collect();
-
-			// Rety failures
-			let mut retry: Vec<BulkOperation<_>> = Vec::new();
-			for f in failed {
-				let id = f["index"]["_id"].as_str().unwrap();
-				let post = posts
+		// Items the cluster rejected under load are sent again after a backoff; any other
+		// failure fails the whole request.
+		let mut pending: Vec<&(String, Value)> = docs.iter().collect();
+		let mut attempt = 0;
+		loop {
+			// A create the cluster applied but whose answer was lost would conflict when sent again.
+			let response = self.resilience.call_once(Operation::Write, |timeout| {
+				// Creating rather than indexing keeps a post from replacing another tenant's post
+				// with the same id.
+				let body: Vec<BulkOperation<_>> = pending
 					.iter()
-					.find(|p| p.id().to_string() == id)
-					.unwrap();
-				retry.push(BulkOperation::index(post).id(id).routing(id).into());
-			}
+					.map(|(id, doc)| BulkOperation::create(id, doc).routing(id).into())
+					.collect();
+				client
+					.bulk(BulkParts::Index(&self.index))
+					.body(body)
+					.request_timeout(timeout)
+					.send()
+			}).await?;
 
-			let response = client
-				.bulk(BulkParts::Index(self.index))
-				.body(retry)
-				.send()
-				.await
-				.unwrap();
+			if !response.status_code().is_success() {
+				let status = response.status_code().as_u16();
+				let message = if matches!(status, 502..=504) {
+					format!("Elasticsearch answered {}, the posts may still have been indexed", response.status_code())
+				} else {
+					format!("Failed to index posts: {}", response.text().await.unwrap_or_default())
+				};
+				return Err(Custom(Status::new(status), json!({"success": false, "message": message})));
+			}
 
 			let json: Value = match response.json().await {
 				Ok(json) => json,
@@ -87,30 +84,64 @@ impl PostRepository for PostRepositoryImpl <'_> {
 				}
 			};
 
-			if json["errors"].as_bool().unwrap() {
-				return Err(Custom(Status::InternalServerError, json!({"success": false, "message": "Failed to index posts"})));
+			if !json["errors"].as_bool().unwrap_or(false) {
+				break;
 			}
+
+			let failed: Vec<&Value> = json["items"]
+				.as_array()
+				.map(|items| items.iter().map(|v| &v["create"]).filter(|v| !v["error"].is_null()).collect())
+				.unwrap_or_default();
+
+			for f in &failed {
+				let status = f["status"].as_u64().map(|status| status.to_string()).unwrap_or_default();
+				self.resilience.metrics().bulk_item_failures.with_label_values(&[&status]).inc();
+			}
+
+			if let Some(conflict) = failed.iter().find(|f| f["status"] == 409) {
+				return Err(Custom(Status::Conflict, json!({
+					"success": false,
+					"message": format!("Post {} already exists", conflict["_id"].as_str().unwrap_or_default())
+				})));
+			}
+
+			if failed.iter().any(|f| f["error"]["type"] == "cluster_block_exception") {
+				return Err(Self::migrating());
+			}
+
+			let retryable = failed.iter().all(|f| matches!(f["status"].as_u64(), Some(429 | 502..=504)));
+			if !retryable || attempt >= self.resilience.max_retries() {
+				return Err(Custom(Status::InternalServerError, json!({
+					"success": false,
+					"message": format!("Failed to index posts: {}", failed
+						.first()
+						.and_then(|f| f["error"]["reason"].as_str())
+						.unwrap_or_default())
+				})));
+			}
+
+			let failed_ids: Vec<&str> = failed.iter().filter_map(|f| f["_id"].as_str()).collect();
+			pending.retain(|(id, _)| failed_ids.contains(&id.as_str()));
+			self.resilience.metrics().elasticsearch_retries.with_label_values(&[Operation::Write.label()]).inc_by(pending.len() as u64);
+			let delay = self.resilience.backoff(attempt);
+			attempt += 1;
+			println!("Retrying {} rejected posts in {}ms", pending.len(), delay.as_millis());
+			tokio::time::sleep(delay).await;
 		}
-			
+
         Ok("Post indexed successfully".to_string())
     }
 
 	async fn set_refresh_interval(&self, interval: Value) -> Result<(), Custom<sea_orm::prelude::Json>> {
-		let client = match &self.client {
-			Ok(client) => client,
-			Err(e) => return Err(Custom(Status::InternalServerError, json!({"success": false, "message": e.to_string()})))
-		};
-		let response = match client
-			.indices()
-			.put_settings(IndicesPutSettingsParts::Index(&[self.index]))
+		let client = self.client().await?;
+		let index = [self.index.as_str()];
+		let indices = client.indices();
+		let response = self.resilience.call(Operation::Admin, |timeout| indices
+			.put_settings(IndicesPutSettingsParts::Index(&index))
 			.body(json!({"index": {"refresh_interval": interval}}))
+			.request_timeout(timeout)
 			.send()
-			.await {
-				Ok(response) => response,
-				Err(e) => {
-					return Err(Custom(Status::InternalServerError, json!({"success": false, "message": e.to_string()})));
-				}
-			};
+		).await?;
 
 		if !response.status_code().is_success() {
 			println!("Failed to set refresh interval: {}", match response.text().await {
@@ -122,71 +153,80 @@ impl PostRepository for PostRepositoryImpl <'_> {
 		Ok(())
 	}
 
-	async fn check_if_exists(&self, post_id: &Uuid) -> bool {
-		let client = match &self.client {
-			Ok(client) => client,
-			Err(_) => return false
-		};
-		let response = match client
-			.exists(ExistsParts::IndexId(self.index, &post_id.to_string()))
-			.routing(&post_id.to_string())
-			.pretty(true)
+	async fn check_if_exists(&self, post_id: &Uuid, tenant: &str) -> Result<bool, Custom<sea_orm::prelude::Json>> {
+		let client = self.client().await?;
+		// Gets by id ignore alias filters, so the tenant is compared here.
+		let id = post_id.to_string();
+		let response = self.resilience.call(Operation::Search, |timeout| client
+			.get(GetParts::IndexId(&self.index, &id))
+			.routing(&id)
+			._source_includes(&["tenant_id"])
+			.request_timeout(timeout)
 			.send()
-			.await {
-				Ok(response) => response,
-				Err(e) => {
-					println!("Failed to check if post exists: {}", e);
-					return false;
-				}
-			};
+		).await?;
 
-		if response.status_code().is_success() {
-			return true;
+		if response.status_code() == StatusCode::NOT_FOUND {
+			return Ok(false);
+		}
+		if !response.status_code().is_success() {
+			let status = response.status_code().as_u16();
+			return Err(Custom(Status::new(status), json!({
+				"success": false,
+				"message": format!("Failed to check if post exists: {}", response.text().await.unwrap_or_default())
+			})));
 		}
-		false
 
+		let json = response.json::<Value>().await.map_err(|e| Custom(Status::InternalServerError, json!({
+			"success": false,
+			"message": e.to_string()
+		})))?;
+		Ok(serde_json::from_value::<Access>(json["_source"].clone()).is_ok_and(|access| access.tenant() == tenant))
 	}
 
-	async fn create_index_if_not_exists(&self, delete: bool, post: &Json<Post>) -> Result<(), Custom<sea_orm::prelude::Json>> {
-		let client = match &self.client {
-			Ok(client) => client,
-			Err(e) => return Err(Custom(Status::InternalServerError, json!({"message": e.to_string()})))
-		};
-		let exists = client
-        .indices()
-        .exists(IndicesExistsParts::Index(&[self.index]))
-        .send()
-        .await.unwrap();
+	async fn create_index_if_not_exists(&self, delete: bool) -> Result<(), Custom<sea_orm::prelude::Json>> {
+		let client = self.client().await?;
+		let index = [self.index.as_str()];
+		let indices = client.indices();
+		let exists = self.resilience.call(Operation::Admin, |timeout| indices
+			.exists(IndicesExistsParts::Index(&index))
+			.request_timeout(timeout)
+			.send()
+		).await?;
 
 		if exists.status_code().is_success() && delete {
-			println!("Deleting index, {}", self.index);
-			let delete = match client
-				.indices()
-				.delete(IndicesDeleteParts::Index(&[self.index]))
-				.send()
-				.await {
-					Ok(response) => response,
-					Err(e) => {
-						return Err(Custom(Status::InternalServerError, json!({"message": e.to_string()})));
-					}
-				};
-			if !delete.status_code().is_success() {
-				println!("Failed to delete index: {}", match delete.text().await {
-					Ok(text) => text,
-					Err(e) => e.to_string()
-				});
+			// Deleting goes by the concrete name, which differs from the configured one behind an alias.
+			if let Some((concrete, _)) = self.current_index(&client).await? {
+				println!("Deleting index, {}", concrete);
+				let concrete = [concrete.as_str()];
+				let delete = self.resilience.call(Operation::Admin, |timeout| indices
+					.delete(IndicesDeleteParts::Index(&concrete))
+					.request_timeout(timeout)
+					.send()
+				).await?;
+				if !delete.status_code().is_success() {
+					println!("Failed to delete index: {}", match delete.text().await {
+						Ok(text) => text,
+						Err(e) => e.to_string()
+					});
+				}
 			}
 		}
 
 		if exists.status_code() == StatusCode::NOT_FOUND || delete {
-			println!("Creating index: {}", self.index);
-			let response = client
-				.indices()
-				.create(IndicesCreateParts::Index(self.index))
-				.body(post.base_index())
+			self.ensure_synonyms_set(&client).await?;
+			// Aliases are dropped along with the index they point to.
+			self.tenant_aliases.lock().unwrap().clear();
+
+			let versioned = self.versioned_index(1);
+			println!("Creating index: {} as {}", self.index, versioned);
+			let mut body = Post::base_index(&self.synonyms_set());
+			body["aliases"] = json!({ self.index.as_str(): {} });
+			let response = self.resilience.call(Operation::Admin, |timeout| indices
+				.create(IndicesCreateParts::Index(&versioned))
+				.body(body.clone())
+				.request_timeout(timeout)
 				.send()
-				.await
-				.unwrap();
+			).await?;
 			if !response.status_code().is_success() {
 				println!("Error while creating index");
 			}
@@ -197,24 +237,27 @@ impl PostRepository for PostRepositoryImpl <'_> {
 
     async fn search(
         &self,
-        query: sea_orm::prelude::Json,
-    ) -> Result<Custom<sea_orm::prelude::Json>, Custom<sea_orm::prelude::Json>> {
-		let client = match &self.client {
-			Ok(client) => client,
-			Err(e) => return Err(Custom(Status::InternalServerError, json!({"success": false, "message": e.to_string()})))
-		};
+        mut query: sea_orm::prelude::Json,
+        projection: &Projection,
+        tenant: &str,
+    ) -> Result<SearchResult, Custom<sea_orm::prelude::Json>> {
+		let client = &self.client().await?;
 
-		let mut response = match client
-			.search(SearchParts::Index(&[self.index]))
-			.body(query)
-			.pretty(true)
-			.send()
-			.await {
-				Ok(response) => response,
-				Err(e) => {
-					return Err(Custom(Status::InternalServerError, json!({"success": false, "message": e.to_string()})));
-				}
-			};
+		// Only fetch fields some type may expose; each hit is then trimmed to its own type's
+		// public fields below.
+		query["_source"] = json!({"includes": projection.source_includes()});
+
+		let query = &query;
+		let mut response = self.on_tenant_alias(client, tenant, |alias| async move {
+			let indices = [alias.as_str()];
+			self.resilience.call(Operation::Search, |timeout| client
+				.search(SearchParts::Index(&indices))
+				.body(query.clone())
+				.pretty(true)
+				.request_timeout(timeout)
+				.send()
+			).await
+		}).await?;
 
 		response = match response.error_for_status_code() {
 			Ok(response) => response,
@@ -237,42 +280,44 @@ impl PostRepository for PostRepositoryImpl <'_> {
 			.unwrap()
 			.iter()
 			.map(|hit| serde_json::from_value(json!({
-				"post": hit.get("_source").unwrap_or(&json!({})),
+				"post": projection.apply(hit.get("_source").unwrap_or(&json!({}))),
 				"highlight": hit.get("highlight").unwrap_or(&json!({})),
+				"explanation": hit.get("_explanation"),
 			}).clone()).unwrap())
 			.collect();
 
-		Ok(Custom(Status::Ok, json!(posts)))
+		// Without a total, e.g. when totals are not tracked, an empty page counts as no match.
+		let total = json["hits"]["total"]["value"].as_u64();
+		if total.map_or(posts.is_empty(), |total| total == 0) {
+			self.resilience.metrics().zero_result_searches.inc();
+		}
+
+		Ok(SearchResult {
+			posts,
+			aggregations: json["aggregations"].clone(),
+		})
     }
 
     async fn update(
         &self,
         post_id: &Uuid,
         post: Json<Post>,
+        tenant: &str,
     ) -> Result<Custom<sea_orm::prelude::Json>, Custom<sea_orm::prelude::Json>> {
-		  let client = match &self.client {
-			Ok(client) => client,
-			Err(e) => return Err(Custom(Status::InternalServerError, json!({"success": false, "message": e.to_string()})))
-		};
+		  let client = &self.client().await?;
+		let body = &post.update_by_query();
 
-		let response = match client
-			.update_by_query(UpdateByQueryParts::Index(&[self.index]))
-			.body(post.update_by_query())
-			.pretty(true)
-			.send()
-			.await {
-				Ok(response) => response,
-				Err(e) => {
-					return Err(Custom(Status::InternalServerError, json!({"success": false, "message": e.to_string()})));
-				}
-			};
-		
-		match response.error_for_status_code() {
-			Ok(response) => response,
-			Err(e) => {
-				return Err(Custom(Status::InternalServerError, json!({"success": false, "message": e.to_string()})));
-			}
-		};
+		let response = self.on_tenant_alias(client, tenant, |alias| async move {
+			let indices = [alias.as_str()];
+			self.resilience.call(Operation::Write, |timeout| client
+				.update_by_query(UpdateByQueryParts::Index(&indices))
+				.body(body.clone())
+				.pretty(true)
+				.request_timeout(timeout)
+				.send()
+			).await
+		}).await?;
+		Self::written(response).await?;
 		
 		Ok(Custom(Status::Ok, json!({
 			"success": true,
@@ -283,39 +328,777 @@ impl PostRepository for PostRepositoryImpl <'_> {
     async fn delete(
         &self,
         post_id: &Uuid,
+        tenant: &str,
     ) -> Result<Custom<sea_orm::prelude::Json>, Custom<sea_orm::prelude::Json>> {
-		  let client =  match &self.client {
-			Ok(client) => client,
-			Err(e) => return Err(Custom(Status::InternalServerError, json!({"success": false, "message": e.to_string()})))	
-		  };
-
-		  let response = match client
-		  	.delete_by_query(DeleteByQueryParts::Index(&[self.index]))
-			.body(json!({
-				"query": {
-					"match": {
-						"id": post_id.to_string()
+		  let client = &self.client().await?;
+
+		  let response = self.on_tenant_alias(client, tenant, |alias| async move {
+			let indices = [alias.as_str()];
+			self.resilience.call(Operation::Write, |timeout| client
+				.delete_by_query(DeleteByQueryParts::Index(&indices))
+				.body(json!({
+					"query": {
+						"match": {
+							"id": post_id.to_string()
+						}
 					}
+				}))
+				.pretty(true)
+				.request_timeout(timeout)
+				.send()
+			).await
+		  }).await?;
+			Self::written(response).await?;
+
+			Ok(Custom(Status::Ok, json!({
+				"success": true,
+			})))
+    }
+	async fn get_posts(&self, ids: &[Uuid], tenant: &str) -> Result<Vec<Post>, Custom<sea_orm::prelude::Json>> {
+		if ids.is_empty() {
+			return Ok(Vec::new());
+		}
+
+		let client = self.client().await?;
+
+		// Multi-get is realtime, so posts indexed a moment ago are found even while
+		// refresh is disabled for bulk indexing.
+		let docs: Vec<Value> = ids
+			.iter()
+			.map(|id| json!({"_id": id.to_string(), "routing": id.to_string()}))
+			.collect();
+
+		let response = self.resilience.call(Operation::Search, |timeout| client
+			.mget(MgetParts::Index(&self.index))
+			.body(json!({"docs": docs}))
+			.request_timeout(timeout)
+			.send()
+		).await?;
+
+		let response = match response.error_for_status_code() {
+			Ok(response) => response,
+			Err(e) => {
+				return Err(Custom(Status::InternalServerError, json!({"success": false, "message": e.to_string()})));
+			}
+		};
+
+		let json: Value = match response.json().await {
+			Ok(json) => json,
+			Err(e) => {
+				return Err(Custom(Status::InternalServerError, json!({"success": false, "message": e.to_string()})));
+			}
+		};
+
+		Ok(json["docs"]
+			.as_array()
+			.map(|docs| docs
+				.iter()
+				.filter_map(|doc| serde_json::from_value::<Post>(doc["_source"].clone()).ok())
+				// Multi-get ignores alias filters, so other tenants' posts are dropped here.
+				.filter(|post| post.access().tenant() == tenant)
+				.collect())
+			.unwrap_or_default())
+	}
+
+	async fn start_update_by_query(&self, body: sea_orm::prelude::Json, tenant: &str) -> Result<String, Custom<sea_orm::prelude::Json>> {
+		let client = &self.client().await?;
+
+		// The cluster keeps the opaque id with the task, which is how get_task knows whose it is.
+		let owner = HeaderValue::from_str(&task_owner(tenant)).map_err(|e| Custom(Status::InternalServerError, json!({
+			"success": false,
+			"message": e.to_string()
+		})))?;
+		let (owner, body) = (&owner, &body);
+		let response = self.on_tenant_alias(client, tenant, |alias| async move {
+			let indices = [alias.as_str()];
+			self.resilience.call(Operation::Write, |timeout| client
+				.update_by_query(UpdateByQueryParts::Index(&indices))
+				.header(HeaderName::from_static("x-opaque-id"), owner.clone())
+				.body(body.clone())
+				.conflicts(Conflicts::Proceed)
+				.refresh(true)
+				.wait_for_completion(false)
+				.request_timeout(timeout)
+				.send()
+			).await
+		}).await?;
+
+		let response = match response.error_for_status_code() {
+			Ok(response) => response,
+			Err(e) => {
+				return Err(Custom(Status::InternalServerError, json!({"success": false, "message": e.to_string()})));
+			}
+		};
+
+		let json: Value = match response.json().await {
+			Ok(json) => json,
+			Err(e) => {
+				return Err(Custom(Status::InternalServerError, json!({"success": false, "message": e.to_string()})));
+			}
+		};
+
+		match json["task"].as_str() {
+			Some(task) => Ok(task.to_string()),
+			None => Err(Custom(Status::InternalServerError, json!({"success": false, "message": "Update by query did not return a task"})))
+		}
+	}
+
+	async fn get_task(&self, task_id: &str, tenant: Option<&str>) -> Result<Custom<sea_orm::prelude::Json>, Custom<sea_orm::prelude::Json>> {
+		let client = self.client().await?;
+
+		let path = format!("/_tasks/{}", task_id);
+		let response = self.resilience.call(Operation::Admin, |timeout| client
+			.send(
+				Method::Get,
+				&path,
+				HeaderMap::new(),
+				None::<&()>,
+				None::<JsonBody<Value>>,
+				Some(timeout),
+			)
+		).await?;
+
+		if response.status_code() == StatusCode::NOT_FOUND {
+			return Err(Custom(Status::NotFound, json!({"success": false, "message": format!("Task {} not found", task_id)})));
+		}
+
+		let response = match response.error_for_status_code() {
+			Ok(response) => response,
+			Err(e) => {
+				return Err(Custom(Status::InternalServerError, json!({"success": false, "message": e.to_string()})));
+			}
+		};
+
+		let json: Value = match response.json().await {
+			Ok(json) => json,
+			Err(e) => {
+				return Err(Custom(Status::InternalServerError, json!({"success": false, "message": e.to_string()})));
+			}
+		};
+
+		// Other tenants' tasks are reported missing rather than forbidden, so ids cannot be probed.
+		if tenant.is_some_and(|tenant| json["task"]["headers"]["X-Opaque-Id"] != task_owner(tenant).as_str()) {
+			return Err(Custom(Status::NotFound, json!({"success": false, "message": format!("Task {} not found", task_id)})));
+		}
+
+		let status = &json["task"]["status"];
+		Ok(Custom(Status::Ok, json!({
+			"success": json["error"].is_null() && json["response"]["failures"].as_array().is_none_or(|f| f.is_empty()),
+			"task": task_id,
+			"completed": json["completed"].as_bool().unwrap_or(false),
+			"total": status["total"],
+			"updated": status["updated"],
+			"version_conflicts": status["version_conflicts"],
+			"failures": json["response"]["failures"],
+			"error": json["error"]
+		})))
+	}
+
+	async fn maps_field(&self, field: &str) -> Result<bool, Custom<sea_orm::prelude::Json>> {
+		if self.mapped_fields.lock().unwrap().contains(field) {
+			return Ok(true);
+		}
+
+		let client = self.client().await?;
+		let index = [self.index.as_str()];
+		let fields = [field];
+		let indices = client.indices();
+		let response = self.resilience.call(Operation::Search, |timeout| indices
+			.get_field_mapping(IndicesGetFieldMappingParts::IndexFields(&index, &fields))
+			.request_timeout(timeout)
+			.send()
+		).await?;
+		if response.status_code() == StatusCode::NOT_FOUND {
+			return Ok(false);
+		}
+
+		let json: Value = match response.error_for_status_code() {
+			Ok(response) => response.json().await.map_err(|e| Custom(Status::InternalServerError, json!({
+				"success": false,
+				"message": e.to_string()
+			})))?,
+			Err(e) => {
+				return Err(Custom(Status::InternalServerError, json!({"success": false, "message": e.to_string()})));
+			}
+		};
+		let mapped = json.as_object().is_some_and(|indices| {
+			!indices.is_empty() && indices.values().all(|definition| definition["mappings"].get(field).is_some())
+		});
+		if mapped {
+			self.mapped_fields.lock().unwrap().insert(field.to_string());
+		}
+		Ok(mapped)
+	}
+
+	async fn check_writable(&self) -> Result<(), Custom<sea_orm::prelude::Json>> {
+		let client = self.client().await?;
+		let index = [self.index.as_str()];
+		let indices = client.indices();
+		let response = self.resilience.call(Operation::Search, |timeout| indices
+			.get_settings(IndicesGetSettingsParts::IndexName(&index, &["index.blocks.write"]))
+			.request_timeout(timeout)
+			.send()
+		).await?;
+		if response.status_code() == StatusCode::NOT_FOUND {
+			return Ok(());
+		}
+
+		let json: Value = match response.error_for_status_code() {
+			Ok(response) => response.json().await.map_err(|e| Custom(Status::InternalServerError, json!({
+				"success": false,
+				"message": e.to_string()
+			})))?,
+			Err(e) => {
+				return Err(Custom(Status::InternalServerError, json!({"success": false, "message": e.to_string()})));
+			}
+		};
+		if json.as_object().into_iter().flatten().any(|(_, definition)| Self::write_blocked(definition)) {
+			return Err(Self::migrating());
+		}
+		Ok(())
+	}
+
+	async fn start_reindex(&self) -> Result<Migration, Custom<sea_orm::prelude::Json>> {
+		let client = self.client().await?;
+		let Some((source, definition)) = self.current_index(&client).await? else {
+			return Err(Custom(Status::NotFound, json!({"success": false, "message": format!("Index {} does not exist", self.index)})));
+		};
+		if Self::write_blocked(&definition) {
+			return Err(Custom(Status::Conflict, json!({
+				"success": false,
+				"message": format!("{} refuses writes, a migration from it is running. If it was abandoned, reset index.blocks.write on {}", source, source)
+			})));
+		}
+		// An index created before versioning counts as version 0.
+		let version = source
+			.strip_prefix(&format!("{}-v", self.index))
+			.and_then(|version| version.parse::<u32>().ok())
+			.unwrap_or(0);
+		let target = self.versioned_index(version + 1);
+
+		// Creating the target is what only one migration can do, so it comes before the block.
+		self.ensure_synonyms_set(&client).await?;
+		let indices = client.indices();
+		let response = self.resilience.call_once(Operation::Admin, |timeout| indices
+			.create(IndicesCreateParts::Index(&target))
+			.body(Post::base_index(&self.synonyms_set()))
+			.request_timeout(timeout)
+			.send()
+		).await?;
+		if response.status_code() == StatusCode::BAD_REQUEST {
+			return Err(Custom(Status::Conflict, json!({
+				"success": false,
+				"message": format!("Cannot create {}, delete it if a previous migration to it failed: {}", target, response.text().await.unwrap_or_default())
+			})));
+		}
+		if let Err(e) = response.error_for_status_code() {
+			return Err(Custom(Status::InternalServerError, json!({"success": false, "message": e.to_string()})));
+		}
+
+		match self.start_copy(&client, &source, &target).await {
+			Ok(task) => Ok(Migration { source, target, task }),
+			Err(e) => {
+				if let Err(unblock) = self.set_write_block(&client, &source, false).await {
+					println!("Failed to let {} take writes again: {}", source, unblock.1["message"]);
 				}
-			}))
-			.pretty(true)
+				Err(e)
+			}
+		}
+	}
+
+	async fn switch_index(&self, migration: &Migration) -> Result<(), Custom<sea_orm::prelude::Json>> {
+		let client = self.client().await?;
+		let Some((current, definition)) = self.current_index(&client).await? else {
+			return Err(Custom(Status::NotFound, json!({"success": false, "message": format!("Index {} does not exist", self.index)})));
+		};
+		if current != migration.source {
+			return Err(Custom(Status::Conflict, json!({
+				"success": false,
+				"message": format!("{} now points to {} instead of {}", self.index, current, migration.source)
+			})));
+		}
+
+		// Every alias moves with its filter, and the old index keeps none, so it can be deleted
+		// once the new one is trusted.
+		let aliases = definition["aliases"].as_object().cloned().unwrap_or_default();
+		let mut actions: Vec<Value> = aliases
+			.iter()
+			.map(|(alias, properties)| {
+				let mut add = properties.clone();
+				add["index"] = json!(migration.target);
+				add["alias"] = json!(alias);
+				json!({"add": add})
+			})
+			.collect();
+		if current == self.index {
+			// An index created before versioning has the configured name itself, which the alias
+			// can only take over by deleting it in the same step.
+			actions.push(json!({"add": {"index": migration.target, "alias": self.index}}));
+			actions.push(json!({"remove_index": {"index": current}}));
+		} else if !aliases.is_empty() {
+			actions.push(json!({"remove": {"index": current, "aliases": aliases.keys().collect::<Vec<_>>()}}));
+		}
+
+		let indices = client.indices();
+		let response = self.resilience.call_once(Operation::Admin, |timeout| indices
+			.update_aliases()
+			.body(json!({"actions": actions}))
+			.request_timeout(timeout)
 			.send()
-			.await {
-				Ok(response) => response,
-				Err(e) => {
-					return Err(Custom(Status::InternalServerError, json!({"success": false, "message": e.to_string()})));
+		).await?;
+		if let Err(e) = response.error_for_status_code() {
+			return Err(Custom(Status::InternalServerError, json!({"success": false, "message": e.to_string()})));
+		}
+		Ok(())
+	}
+
+	async fn abort_reindex(&self, migration: &Migration) -> Result<(), Custom<sea_orm::prelude::Json>> {
+		let client = self.client().await?;
+		self.set_write_block(&client, &migration.source, false).await
+	}
+
+	async fn get_synonyms(&self) -> Result<Vec<String>, Custom<sea_orm::prelude::Json>> {
+		let client = self.client().await?;
+
+		let path = format!("/_synonyms/{}", self.synonyms_set());
+		let response = self.resilience.call(Operation::Admin, |timeout| client
+			.send(
+				Method::Get,
+				&path,
+				HeaderMap::new(),
+				Some(&[("size", 10000)]),
+				None::<JsonBody<Value>>,
+				Some(timeout),
+			)
+		).await?;
+
+		if response.status_code() == StatusCode::NOT_FOUND {
+			return Ok(Vec::new());
+		}
+
+		let response = match response.error_for_status_code() {
+			Ok(response) => response,
+			Err(e) => {
+				return Err(Custom(Status::InternalServerError, json!({"success": false, "message": e.to_string()})));
+			}
+		};
+
+		let json: Value = match response.json().await {
+			Ok(json) => json,
+			Err(e) => {
+				return Err(Custom(Status::InternalServerError, json!({"success": false, "message": e.to_string()})));
+			}
+		};
+
+		Ok(json["synonyms_set"]
+			.as_array()
+			.map(|rules| rules
+				.iter()
+				.filter_map(|rule| rule["synonyms"].as_str())
+				.map(String::from)
+				.collect())
+			.unwrap_or_default())
+	}
+
+	async fn put_synonyms(&self, rules: &[String]) -> Result<Custom<sea_orm::prelude::Json>, Custom<sea_orm::prelude::Json>> {
+		let client = self.client().await?;
+
+		let synonyms_set: Vec<Value> = rules
+			.iter()
+			.map(|rule| json!({"synonyms": rule}))
+			.collect();
+
+		let path = format!("/_synonyms/{}", self.synonyms_set());
+		let response = self.resilience.call(Operation::Admin, |timeout| client
+			.send(
+				Method::Put,
+				&path,
+				HeaderMap::new(),
+				None::<&()>,
+				Some(JsonBody::new(json!({"synonyms_set": synonyms_set}))),
+				Some(timeout),
+			)
+		).await?;
+
+		if response.status_code().is_client_error() {
+			let json: Value = response.json().await.unwrap_or_default();
+			return Err(Custom(Status::BadRequest, json!({
+				"success": false,
+				"message": json["error"]["reason"].as_str().unwrap_or("Invalid synonym rules")
+			})));
+		}
+
+		if let Err(e) = response.error_for_status_code() {
+			return Err(Custom(Status::InternalServerError, json!({"success": false, "message": e.to_string()})));
+		}
+
+		// Synonyms are only used by search analyzers, so reloading them picks up the new
+		// rules without reindexing.
+		let index = [self.index.as_str()];
+		let indices = client.indices();
+		let reload = self.resilience.call(Operation::Admin, |timeout| indices
+			.reload_search_analyzers(IndicesReloadSearchAnalyzersParts::Index(&index))
+			.request_timeout(timeout)
+			.send()
+		).await?;
+
+		if !reload.status_code().is_success() && reload.status_code() != StatusCode::NOT_FOUND {
+			println!("Failed to reload search analyzers: {}", match reload.text().await {
+				Ok(text) => text,
+				Err(e) => e.to_string()
+			});
+		}
+
+		Ok(Custom(Status::Ok, json!({
+			"success": true,
+			"message": format!("{} synonym rules saved", rules.len())
+		})))
+	}
+
+	async fn client_health(&self) -> HealthCheck {
+		// Gives a client that was never built, or whose backoff has elapsed, a chance to connect.
+		let _ = self.client.get().await;
+		let (up, mut details) = self.client.status().await;
+		let open = self.resilience.is_open();
+		details["circuit"] = json!(if open { "open" } else { "closed" });
+		HealthCheck::new("elasticsearch_client", up && !open, details)
+	}
+
+	async fn cluster_health(&self) -> Vec<HealthCheck> {
+		const CHECKS: [&str; 4] = ["cluster_reachable", "cluster_health", "index", "index_mapping"];
+		let skipped = |reason: &str| -> Vec<HealthCheck> {
+			CHECKS[1..].iter().map(|name| HealthCheck::new(name, false, json!({"error": reason}))).collect()
+		};
+		let Ok(client) = self.client.get().await else {
+			let mut checks = vec![HealthCheck::new(CHECKS[0], false, json!({"error": "Elasticsearch client unavailable"}))];
+			checks.extend(skipped("Elasticsearch client unavailable"));
+			return checks;
+		};
+		// Probes are sent once, without retries, and leave the circuit breaker alone.
+		let timeout = self.resilience.timeout(Operation::Search);
+
+		let info = PostRepositoryImpl::probe(client.info().request_timeout(timeout).send()).await;
+		let reachable = match &info {
+			Ok(json) => HealthCheck::new(CHECKS[0], true, json!({"version": json["version"]["number"]})),
+			Err(e) => HealthCheck::new(CHECKS[0], false, json!({"error": e})),
+		};
+		if info.is_err() {
+			let mut checks = vec![reachable];
+			checks.extend(skipped("cluster unreachable"));
+			return checks;
+		}
+
+		let cluster = client.cluster();
+		let health = match PostRepositoryImpl::probe(cluster.health(ClusterHealthParts::None).request_timeout(timeout).send()).await {
+			Ok(json) => HealthCheck::new(CHECKS[1], json["status"] != "red", json!({
+				"color": json["status"],
+				"nodes": json["number_of_nodes"],
+				"unassigned_shards": json["unassigned_shards"]
+			})),
+			Err(e) => HealthCheck::new(CHECKS[1], false, json!({"error": e})),
+		};
+
+		let index = [self.index.as_str()];
+		let indices = client.indices();
+		let definition = match indices.get(IndicesGetParts::Index(&index)).request_timeout(timeout).send().await {
+			Ok(response) if response.status_code() == StatusCode::NOT_FOUND => Ok(None),
+			response => PostRepositoryImpl::probe(async { response }).await.map(Some),
+		};
+		let (index_check, mapping_check) = match definition {
+			// The index is created when the first posts are indexed.
+			Ok(None) => (
+				HealthCheck::new(CHECKS[2], true, json!({"exists": false})),
+				HealthCheck::new(CHECKS[3], true, json!({"conflicts": []})),
+			),
+			Ok(Some(json)) => {
+				let definition = json.as_object().and_then(|indices| indices.values().next()).cloned().unwrap_or_default();
+				let aliases: Vec<String> = definition["aliases"]
+					.as_object()
+					.map(|aliases| aliases.keys().cloned().collect())
+					.unwrap_or_default();
+				// Aliases deleted behind this service's back stay missing until the next request
+				// for their tenant creates them again.
+				let missing: Vec<String> = self
+					.tenant_aliases
+					.lock()
+					.unwrap()
+					.iter()
+					.map(|tenant| self.tenant_alias(tenant))
+					.filter(|alias| !aliases.contains(alias))
+					.collect();
+				let conflicts = Post::mapping_conflicts(&definition["mappings"]);
+				if !conflicts.is_empty() && !self.mapping_warned.swap(true, Ordering::Relaxed) {
+					println!(
+						"Warning: index {} lacks mappings this version expects ({}). Mappings and analyzers only apply to new indices; POST /admin/reindex copies the posts into one.",
+						self.index,
+						conflicts.join("; ")
+					);
 				}
-			};
+				(
+					HealthCheck::new(CHECKS[2], missing.is_empty(), json!({"exists": true, "aliases": aliases, "missing_aliases": missing})),
+					HealthCheck::new(CHECKS[3], conflicts.is_empty(), json!({"conflicts": conflicts})),
+				)
+			}
+			Err(e) => (
+				HealthCheck::new(CHECKS[2], false, json!({"error": e})),
+				HealthCheck::new(CHECKS[3], false, json!({"error": e})),
+			),
+		};
+
+		vec![reachable, health, index_check, mapping_check]
+	}
+}
+
+impl PostRepositoryImpl {
+	async fn client(&self) -> Result<Elasticsearch, Custom<sea_orm::prelude::Json>> {
+		self.client
+			.get()
+			.await
+			.map_err(|e| Custom(Status::ServiceUnavailable, json!({"success": false, "message": e})))
+	}
+
+	/// The index behind the configured name, with its definition: the versioned index the alias
+	/// points to, or the index with that name when it was created before versioning. `None`
+	/// before the first post is indexed.
+	async fn current_index(&self, client: &Elasticsearch) -> Result<Option<(String, Value)>, Custom<sea_orm::prelude::Json>> {
+		let index = [self.index.as_str()];
+		let indices = client.indices();
+		let response = self.resilience.call(Operation::Admin, |timeout| indices
+			.get(IndicesGetParts::Index(&index))
+			.request_timeout(timeout)
+			.send()
+		).await?;
+		if response.status_code() == StatusCode::NOT_FOUND {
+			return Ok(None);
+		}
+
+		let json: Value = match response.error_for_status_code() {
+			Ok(response) => response.json().await.map_err(|e| Custom(Status::InternalServerError, json!({
+				"success": false,
+				"message": e.to_string()
+			})))?,
+			Err(e) => {
+				return Err(Custom(Status::InternalServerError, json!({"success": false, "message": e.to_string()})));
+			}
+		};
+		let mut definitions = json.as_object().cloned().unwrap_or_default().into_iter();
+		match (definitions.next(), definitions.next()) {
+			(Some(current), None) => Ok(Some(current)),
+			(None, _) => Ok(None),
+			(Some(_), Some(_)) => Err(Custom(Status::Conflict, json!({
+				"success": false,
+				"message": format!("{} points to several indices", self.index)
+			}))),
+		}
+	}
+
+	/// Blocks writes to `source`, so none are left behind by the copy, then starts copying it into
+	/// `target`. Returns the copy's task.
+	async fn start_copy(&self, client: &Elasticsearch, source: &str, target: &str) -> Result<String, Custom<sea_orm::prelude::Json>> {
+		self.set_write_block(client, source, true).await?;
+		// The copy reads what was refreshed, which must include the writes acknowledged before
+		// the block.
+		let index = [source];
+		let indices = client.indices();
+		let response = self.resilience.call(Operation::Admin, |timeout| indices
+			.refresh(IndicesRefreshParts::Index(&index))
+			.request_timeout(timeout)
+			.send()
+		).await?;
+		if let Err(e) = response.error_for_status_code() {
+			return Err(Custom(Status::InternalServerError, json!({"success": false, "message": e.to_string()})));
+		}
+
+		let response = self.resilience.call_once(Operation::Admin, |timeout| client
+			.reindex()
+			.body(json!({"source": {"index": source}, "dest": {"index": target}}))
+			.wait_for_completion(false)
+			.request_timeout(timeout)
+			.send()
+		).await?;
+		let json: Value = match response.error_for_status_code() {
+			Ok(response) => response.json().await.map_err(|e| Custom(Status::InternalServerError, json!({
+				"success": false,
+				"message": e.to_string()
+			})))?,
+			Err(e) => {
+				return Err(Custom(Status::InternalServerError, json!({"success": false, "message": e.to_string()})));
+			}
+		};
+		Ok(json["task"].as_str().unwrap_or_default().to_string())
+	}
+
+	/// The block is kept in the cluster, so every instance refuses writes during a migration.
+	async fn set_write_block(&self, client: &Elasticsearch, index: &str, blocked: bool) -> Result<(), Custom<sea_orm::prelude::Json>> {
+		let index = [index];
+		let indices = client.indices();
+		let response = self.resilience.call(Operation::Admin, |timeout| indices
+			.put_settings(IndicesPutSettingsParts::Index(&index))
+			.body(json!({"index": {"blocks": {"write": blocked}}}))
+			.request_timeout(timeout)
+			.send()
+		).await?;
+		if let Err(e) = response.error_for_status_code() {
+			return Err(Custom(Status::InternalServerError, json!({"success": false, "message": e.to_string()})));
+		}
+		Ok(())
+	}
+
+	/// Whether the index `definition` describes refuses writes. Settings are returned as strings.
+	fn write_blocked(definition: &Value) -> bool {
+		matches!(&definition["settings"]["index"]["blocks"]["write"], Value::String(blocked) if blocked == "true")
+	}
+
+	fn migrating() -> Custom<sea_orm::prelude::Json> {
+		Custom(Status::ServiceUnavailable, json!({
+			"success": false,
+			"message": "Posts are being migrated to a new index and cannot be written until it completes"
+		}))
+	}
+
+	/// Checks the response of a by-query write. A write the migration block refused is reported
+	/// as such; it only happens when the migration starts during the write.
+	async fn written(response: Response) -> Result<Response, Custom<sea_orm::prelude::Json>> {
+		if response.status_code() == StatusCode::FORBIDDEN {
+			let text = response.text().await.unwrap_or_default();
+			if text.contains("cluster_block_exception") {
+				return Err(Self::migrating());
+			}
+			return Err(Custom(Status::InternalServerError, json!({"success": false, "message": text})));
+		}
+		response
+			.error_for_status_code()
+			.map_err(|e| Custom(Status::InternalServerError, json!({"success": false, "message": e.to_string()})))
+	}
+
+	/// Body of a health probe's response, or why it failed.
+	async fn probe(response: impl Future<Output = Result<Response, elasticsearch::Error>>) -> Result<Value, String> {
+		let response = response.await.map_err(|e| e.to_string())?;
+		let response = response.error_for_status_code().map_err(|e| e.to_string())?;
+		response.json().await.map_err(|e| e.to_string())
+	}
+
+	/// Sends the request built by `send` to `tenant`'s alias. An alias deleted behind this
+	/// service's back answers 404: it is then created again and the request sent once more.
+	async fn on_tenant_alias<F, Fut>(&self, client: &Elasticsearch, tenant: &str, send: F) -> Result<Response, Custom<sea_orm::prelude::Json>>
+	where
+		F: Fn(String) -> Fut,
+		Fut: Future<Output = Result<Response, Custom<sea_orm::prelude::Json>>>,
+	{
+		let alias = self.ensure_tenant_alias(client, tenant).await?;
+		let response = send(alias.clone()).await?;
+		if response.status_code() != StatusCode::NOT_FOUND || !self.tenant_aliases.lock().unwrap().remove(tenant) {
+			return Ok(response);
+		}
 
-			match response.error_for_status_code() {
-				Ok(response) => response,
+		println!("Alias {} is missing, creating it again", alias);
+		let alias = self.ensure_tenant_alias(client, tenant).await?;
+		send(alias).await
+	}
+
+	/// Creates the alias filtering the index down to `tenant`'s documents the first time the
+	/// tenant is seen, and returns its name. Searches and by-query writes only go through it.
+	///
+	/// Tenants beyond `max_tenant_aliases`, counting the aliases other instances created, are
+	/// refused rather than given an alias.
+	async fn ensure_tenant_alias(&self, client: &Elasticsearch, tenant: &str) -> Result<String, Custom<sea_orm::prelude::Json>> {
+		let alias = self.tenant_alias(tenant);
+		if self.tenant_aliases.lock().unwrap().contains(tenant) {
+			return Ok(alias);
+		}
+
+		let index = [self.index.as_str()];
+		let indices = client.indices();
+		let pattern = [format!("{}*", self.tenant_alias(""))];
+		let pattern = [pattern[0].as_str()];
+		let existing = self.resilience.call(Operation::Admin, |timeout| indices
+			.get_alias(IndicesGetAliasParts::IndexName(&index, &pattern))
+			.request_timeout(timeout)
+			.send()
+		).await?;
+		// Not found when the index or every alias is missing.
+		let existing: Vec<String> = match existing.status_code() {
+			StatusCode::NOT_FOUND => Vec::new(),
+			_ => match existing.error_for_status_code() {
+				Ok(response) => response
+					.json::<Value>()
+					.await
+					.ok()
+					.and_then(|json| json.as_object().and_then(|indices| indices.values().next()).cloned())
+					.and_then(|definition| definition["aliases"].as_object().map(|aliases| aliases.keys().cloned().collect()))
+					.unwrap_or_default(),
 				Err(e) => {
 					return Err(Custom(Status::InternalServerError, json!({"success": false, "message": e.to_string()})));
 				}
-			};
+			}
+		};
+		if existing.contains(&alias) {
+			self.tenant_aliases.lock().unwrap().insert(tenant.to_string());
+			return Ok(alias);
+		}
+		if existing.len() >= self.max_tenant_aliases {
+			println!("Refusing tenant {}: the index already has {} tenant aliases", tenant, existing.len());
+			return Err(Custom(Status::Forbidden, json!({
+				"success": false,
+				"message": format!("Tenant {} cannot be added: the limit of {} tenants is reached", tenant, self.max_tenant_aliases)
+			})));
+		}
 
-			Ok(Custom(Status::Ok, json!({
-				"success": true,
-			})))
-    }
+		let response = self.resilience.call(Operation::Admin, |timeout| indices
+			.put_alias(IndicesPutAliasParts::IndexName(&index, &alias))
+			.body(json!({"filter": Access::tenant_filter(tenant)}))
+			.request_timeout(timeout)
+			.send()
+		).await?;
+
+		// Without an index there is nothing to alias yet; the caller's request fails on its own.
+		if response.status_code() == StatusCode::NOT_FOUND {
+			return Ok(alias);
+		}
+
+		if let Err(e) = response.error_for_status_code() {
+			return Err(Custom(Status::InternalServerError, json!({"success": false, "message": e.to_string()})));
+		}
+
+		self.tenant_aliases.lock().unwrap().insert(tenant.to_string());
+		Ok(alias)
+	}
+
+	/// The synonyms set must exist before an index whose analyzers reference it is created.
+	async fn ensure_synonyms_set(&self, client: &Elasticsearch) -> Result<(), Custom<sea_orm::prelude::Json>> {
+		let path = format!("/_synonyms/{}", self.synonyms_set());
+		let exists = self.resilience.call(Operation::Admin, |timeout| client
+			.send(Method::Get, &path, HeaderMap::new(), None::<&()>, None::<JsonBody<Value>>, Some(timeout))
+		).await?;
+
+		if exists.status_code() != StatusCode::NOT_FOUND {
+			return Ok(());
+		}
+
+		println!("Creating synonyms set: {}", self.synonyms_set());
+		let response = self.resilience.call(Operation::Admin, |timeout| client
+			.send(
+				Method::Put,
+				&path,
+				HeaderMap::new(),
+				None::<&()>,
+				Some(JsonBody::new(json!({"synonyms_set": []}))),
+				Some(timeout),
+			)
+		).await?;
+
+		if !response.status_code().is_success() {
+			println!("Failed to create synonyms set: {}", match response.text().await {
+				Ok(text) => text,
+				Err(e) => e.to_string()
+			});
+		}
+
+		Ok(())
+	}
+}
+
+/// Opaque id of the tasks started for `tenant`.
+fn task_owner(tenant: &str) -> String {
+	format!("tenant:{}", tenant)
 }
@@ -0,0 +1,355 @@
+use std::{
+    future::Future,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use elasticsearch::http::response::Response;
+use rand::Rng;
+use rocket::{
+    http::Status,
+    response::status::Custom,
+    serde::json::{json, Value},
+};
+
+use super::{
+    config::{CircuitBreakerSettings, ElasticsearchSettings, RetrySettings, TimeoutSettings},
+    metrics::Metrics,
+};
+
+/// Kind of request sent to the cluster, which sets its timeout.
+#[derive(Debug, Clone, Copy)]
+pub enum Operation {
+    Search,
+    Write,
+    Admin,
+}
+
+impl Operation {
+    pub fn label(self) -> &'static str {
+        match self {
+            Operation::Search => "search",
+            Operation::Write => "write",
+            Operation::Admin => "admin",
+        }
+    }
+}
+
+/// Sends requests to the cluster with a timeout per operation, retries the ones worth retrying
+/// and stops sending any while the cluster is not answering.
+pub struct Resilience {
+    timeouts: TimeoutSettings,
+    retry: RetrySettings,
+    breaker: CircuitBreaker,
+    metrics: Arc<Metrics>,
+}
+
+impl Resilience {
+    pub fn new(settings: &ElasticsearchSettings, metrics: Arc<Metrics>) -> Self {
+        Resilience {
+            timeouts: settings.timeouts.clone(),
+            retry: settings.retry.clone(),
+            breaker: CircuitBreaker::new(settings.circuit_breaker.clone()),
+            metrics,
+        }
+    }
+
+    pub fn timeout(&self, operation: Operation) -> Duration {
+        Duration::from_secs(match operation {
+            Operation::Search => self.timeouts.search_secs,
+            Operation::Write => self.timeouts.write_secs,
+            Operation::Admin => self.timeouts.admin_secs,
+        })
+    }
+
+    pub fn max_retries(&self) -> u32 {
+        self.retry.max_retries
+    }
+
+    pub fn metrics(&self) -> &Metrics {
+        &self.metrics
+    }
+
+    /// Delay before retry number `attempt` (from 0): a random duration up to an exponentially
+    /// growing cap, so clients that failed together do not retry together.
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let cap = self
+            .retry
+            .initial_backoff_ms
+            .saturating_mul(1 << attempt.min(20))
+            .min(self.retry.max_backoff_ms);
+        Duration::from_millis(rand::thread_rng().gen_range(0..=cap))
+    }
+
+    /// Whether requests currently fail fast.
+    pub fn is_open(&self) -> bool {
+        self.breaker.is_open()
+    }
+
+    /// Sends the request built by `send`, which is given the operation's timeout and called again
+    /// for each retry.
+    ///
+    /// Requests that timed out are not retried: the cluster may have applied them.
+    pub async fn call<F, Fut>(&self, operation: Operation, send: F) -> Result<Response, Custom<Value>>
+    where
+        F: Fn(Duration) -> Fut,
+        Fut: Future<Output = Result<Response, elasticsearch::Error>>,
+    {
+        self.send(operation, true, send).await
+    }
+
+    /// Like [`Resilience::call`], for requests that must not be applied twice: they are only sent
+    /// again when the cluster refused them with a 429 or could not be reached. A 502-504 may come
+    /// from a proxy after the cluster applied the request.
+    pub async fn call_once<F, Fut>(&self, operation: Operation, send: F) -> Result<Response, Custom<Value>>
+    where
+        F: Fn(Duration) -> Fut,
+        Fut: Future<Output = Result<Response, elasticsearch::Error>>,
+    {
+        self.send(operation, false, send).await
+    }
+
+    async fn send<F, Fut>(&self, operation: Operation, retry_gateway_errors: bool, send: F) -> Result<Response, Custom<Value>>
+    where
+        F: Fn(Duration) -> Fut,
+        Fut: Future<Output = Result<Response, elasticsearch::Error>>,
+    {
+        let timeout = self.timeout(operation);
+        let mut attempt = 0;
+        loop {
+            if let Some(open_for) = self.breaker.acquire() {
+                return Err(Custom(Status::ServiceUnavailable, json!({
+                    "success": false,
+                    "message": format!("Elasticsearch is not answering, failing fast for {}s", open_for.as_secs() + 1)
+                })));
+            }
+
+            let started = Instant::now();
+            let result = send(timeout).await;
+            let outcome = match &result {
+                Ok(response) => response.status_code().as_u16().to_string(),
+                Err(e) if e.is_timeout() => "timeout".into(),
+                Err(e) if is_connect(e) => "connect_error".into(),
+                Err(_) => "error".into(),
+            };
+            self.metrics
+                .elasticsearch_request_duration
+                .with_label_values(&[operation.label(), &outcome])
+                .observe(started.elapsed().as_secs_f64());
+
+            let failure = match result {
+                Ok(response) => {
+                    let code = response.status_code().as_u16();
+                    if matches!(code, 502..=504) {
+                        self.breaker.record_failure();
+                    } else {
+                        self.breaker.record_success();
+                    }
+                    let retryable = code == 429 || (retry_gateway_errors && matches!(code, 502..=504));
+                    if !retryable {
+                        return Ok(response);
+                    }
+                    Custom(Status::new(code), json!({
+                        "success": false,
+                        "message": format!("Elasticsearch answered {}", response.status_code())
+                    }))
+                }
+                Err(e) if e.is_timeout() => {
+                    self.breaker.record_failure();
+                    return Err(Custom(Status::GatewayTimeout, json!({
+                        "success": false,
+                        "message": format!("Elasticsearch did not answer within {}s", timeout.as_secs())
+                    })));
+                }
+                Err(e) if is_connect(&e) => {
+                    self.breaker.record_failure();
+                    Custom(Status::ServiceUnavailable, json!({"success": false, "message": e.to_string()}))
+                }
+                Err(e) => {
+                    return Err(Custom(Status::InternalServerError, json!({"success": false, "message": e.to_string()})));
+                }
+            };
+
+            if attempt >= self.retry.max_retries {
+                return Err(failure);
+            }
+            let delay = self.backoff(attempt);
+            attempt += 1;
+            self.metrics.elasticsearch_retries.with_label_values(&[operation.label()]).inc();
+            println!(
+                "Elasticsearch {:?} request failed ({}), retry {} of {} in {}ms",
+                operation,
+                failure.1["message"].as_str().unwrap_or_default(),
+                attempt,
+                self.retry.max_retries,
+                delay.as_millis()
+            );
+            tokio::time::sleep(delay).await;
+        }
+    }
+}
+
+/// Whether the request never reached the cluster.
+fn is_connect(e: &elasticsearch::Error) -> bool {
+    std::error::Error::source(e)
+        .and_then(|source| source.downcast_ref::<reqwest::Error>())
+        .is_some_and(reqwest::Error::is_connect)
+}
+
+struct CircuitBreaker {
+    settings: CircuitBreakerSettings,
+    state: Mutex<BreakerState>,
+}
+
+#[derive(Default)]
+struct BreakerState {
+    /// Requests in a row that got no answer.
+    failures: u32,
+    /// While set and in the future, requests fail fast.
+    open_until: Option<Instant>,
+}
+
+impl CircuitBreaker {
+    fn new(settings: CircuitBreakerSettings) -> Self {
+        CircuitBreaker {
+            settings,
+            state: Mutex::new(BreakerState::default()),
+        }
+    }
+
+    fn is_open(&self) -> bool {
+        let state = self.state.lock().unwrap();
+        state.open_until.is_some_and(|until| until > Instant::now())
+    }
+
+    /// `None` when a request may be sent, otherwise how long requests still fail fast. Once that
+    /// elapsed, a single request probes the cluster while the others keep failing fast.
+    fn acquire(&self) -> Option<Duration> {
+        let mut state = self.state.lock().unwrap();
+        let now = Instant::now();
+        match state.open_until {
+            Some(until) if until > now => Some(until - now),
+            Some(_) => {
+                state.open_until = Some(now + Duration::from_secs(self.settings.open_secs));
+                None
+            }
+            None => None,
+        }
+    }
+
+    fn record_success(&self) {
+        let mut state = self.state.lock().unwrap();
+        if state.open_until.is_some() {
+            println!("Elasticsearch is answering again, circuit closed");
+        }
+        *state = BreakerState::default();
+    }
+
+    fn record_failure(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.failures += 1;
+        if state.failures == self.settings.failure_threshold {
+            println!(
+                "Elasticsearch failed {} requests in a row, failing fast for {}s",
+                state.failures, self.settings.open_secs
+            );
+        }
+        if state.failures >= self.settings.failure_threshold {
+            state.open_until = Some(Instant::now() + Duration::from_secs(self.settings.open_secs));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn breaker(failure_threshold: u32) -> CircuitBreaker {
+        CircuitBreaker::new(CircuitBreakerSettings { failure_threshold, open_secs: 30 })
+    }
+
+    /// Moves the breaker to the end of its open period.
+    fn elapse(breaker: &CircuitBreaker) {
+        breaker.state.lock().unwrap().open_until = Some(Instant::now());
+    }
+
+    #[test]
+    fn the_breaker_opens_after_failure_threshold_failures_in_a_row() {
+        let breaker = breaker(3);
+
+        breaker.record_failure();
+        breaker.record_failure();
+        assert!(!breaker.is_open());
+        assert_eq!(breaker.acquire(), None);
+
+        breaker.record_failure();
+        assert!(breaker.is_open());
+        let open_for = breaker.acquire().expect("requests should fail fast");
+        assert!(open_for <= Duration::from_secs(30) && open_for > Duration::from_secs(29));
+    }
+
+    #[test]
+    fn a_success_resets_the_failures_in_a_row() {
+        let breaker = breaker(2);
+
+        breaker.record_failure();
+        breaker.record_success();
+        breaker.record_failure();
+        assert!(!breaker.is_open());
+    }
+
+    #[test]
+    fn a_single_probe_is_let_through_once_the_breaker_was_open_long_enough() {
+        let breaker = breaker(1);
+        breaker.record_failure();
+        elapse(&breaker);
+
+        assert_eq!(breaker.acquire(), None);
+        assert!(breaker.acquire().is_some());
+        assert!(breaker.acquire().is_some());
+    }
+
+    #[test]
+    fn a_failed_probe_keeps_the_breaker_open() {
+        let breaker = breaker(1);
+        breaker.record_failure();
+        elapse(&breaker);
+
+        assert_eq!(breaker.acquire(), None);
+        breaker.record_failure();
+        assert!(breaker.is_open());
+        assert!(breaker.acquire().is_some());
+    }
+
+    #[test]
+    fn a_successful_probe_closes_the_breaker() {
+        let breaker = breaker(1);
+        breaker.record_failure();
+        elapse(&breaker);
+
+        assert_eq!(breaker.acquire(), None);
+        breaker.record_success();
+        assert!(!breaker.is_open());
+        assert_eq!(breaker.acquire(), None);
+        assert_eq!(breaker.acquire(), None);
+    }
+
+    #[test]
+    fn backoff_never_exceeds_max_backoff_ms() {
+        let settings = ElasticsearchSettings {
+            retry: RetrySettings { max_retries: 3, initial_backoff_ms: 100, max_backoff_ms: 2_000 },
+            ..ElasticsearchSettings::default()
+        };
+        let resilience = Resilience::new(&settings, Arc::new(Metrics::new()));
+
+        for attempt in [0, 1, 4, 5, 20, 63, 64, u32::MAX] {
+            for _ in 0..100 {
+                assert!(resilience.backoff(attempt) <= Duration::from_millis(2_000), "attempt {} backed off too long", attempt);
+            }
+        }
+        for _ in 0..100 {
+            assert!(resilience.backoff(0) <= Duration::from_millis(100));
+            assert!(resilience.backoff(2) <= Duration::from_millis(400));
+        }
+    }
+}
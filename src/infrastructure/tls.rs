@@ -0,0 +1,305 @@
+use std::io::{Error, ErrorKind};
+
+use elasticsearch::{
+    auth::ClientCertificate,
+    cert::{Certificate, CertificateValidation},
+};
+use url::Url;
+
+use super::config::TlsSettings;
+
+/// Header carrying the tunnel secret on requests to pinned nodes.
+pub const TUNNEL_HEADER: &str = "x-pinned-tunnel";
+
+/// How the client validates the certificates nodes present. Full validation against the
+/// system's CAs unless configured otherwise; pinned nodes are validated by their tunnel.
+pub fn certificate_validation(tls: &TlsSettings) -> Result<CertificateValidation, Error> {
+    if tls.accept_invalid_certs {
+        println!("Warning: certificates presented by Elasticsearch are not validated");
+        return Ok(CertificateValidation::None);
+    }
+
+    Ok(match &tls.ca_file {
+        Some(path) => {
+            let pem = std::fs::read(path)?;
+            CertificateValidation::Full(
+                Certificate::from_pem(&pem).map_err(|e| Error::new(ErrorKind::InvalidData, format!("{}: {}", path, e)))?,
+            )
+        }
+        None => CertificateValidation::Default,
+    })
+}
+
+/// The certificate this service presents to the cluster, if any.
+pub fn client_certificate(tls: &TlsSettings) -> Result<Option<ClientCertificate>, Error> {
+    let Some(path) = &tls.client_cert_file else {
+        return Ok(None);
+    };
+    let bytes = std::fs::read(path)?;
+    Ok(Some(ClientCertificate::Pkcs12(bytes, tls.client_cert_password.as_ref().map(|password| password.expose().to_string()))))
+}
+
+/// Replaces the https `urls` with tunnels to them when fingerprints are pinned, and returns the
+/// secret requests through the tunnels must carry in [`TUNNEL_HEADER`].
+///
+/// The transport trusts the system's CAs whatever else it is given, so it cannot enforce pins
+/// itself. Each tunnel listens on a loopback port and connects to its node over TLS, and only
+/// forwards once the node presented a certificate with a pinned fingerprint. This is checked on
+/// every connection, not once at startup. The fingerprint identifies the certificate, so its
+/// chain and hostname are not checked.
+#[cfg(feature = "native-tls")]
+pub fn pin_nodes(tls: &TlsSettings, urls: &mut [Url]) -> Result<Option<String>, Error> {
+    use rand::Rng;
+
+    if tls.pinned_fingerprints.is_empty() {
+        return Ok(None);
+    }
+
+    let mut builder = native_tls::TlsConnector::builder();
+    builder.danger_accept_invalid_certs(true);
+    if let Some(path) = &tls.client_cert_file {
+        let password = tls.client_cert_password.as_ref().map(|password| password.expose()).unwrap_or_default();
+        let identity = native_tls::Identity::from_pkcs12(&std::fs::read(path)?, password)
+            .map_err(|e| Error::new(ErrorKind::InvalidData, format!("{}: {}", path, e)))?;
+        builder.identity(identity);
+    }
+    let connector = tokio_native_tls::TlsConnector::from(builder.build().map_err(Error::other)?);
+
+    // Other processes on this host could connect to the tunnels too, and would borrow the client
+    // certificate; the secret keeps them out.
+    let secret = hex::encode(rand::thread_rng().gen::<[u8; 16]>());
+    let fingerprints: Vec<String> = tls.pinned_fingerprints.iter().map(|pinned| tunnel::normalize(pinned)).collect();
+    for url in urls.iter_mut().filter(|url| url.scheme() == "https") {
+        let host = url.host_str().unwrap_or_default().to_string();
+        let port = url.port_or_known_default().unwrap_or(443);
+        let tunnel = tunnel::Tunnel {
+            host: host.clone(),
+            port,
+            connector: connector.clone(),
+            fingerprints: fingerprints.clone(),
+            secret: secret.clone(),
+        };
+        let address = tunnel.listen()?;
+        println!("Pinned Elasticsearch node {}:{} is reached through {}", host, port, address);
+        // Only the scheme, host and port change, so a path prefix is kept.
+        url.set_scheme("http").unwrap();
+        url.set_ip_host(address.ip()).unwrap();
+        url.set_port(Some(address.port())).unwrap();
+    }
+    Ok(Some(secret))
+}
+
+#[cfg(not(feature = "native-tls"))]
+pub fn pin_nodes(tls: &TlsSettings, _urls: &mut [Url]) -> Result<Option<String>, Error> {
+    if tls.pinned_fingerprints.is_empty() {
+        return Ok(None);
+    }
+    Err(Error::new(ErrorKind::Unsupported, "certificate pinning requires the native-tls feature"))
+}
+
+#[cfg(feature = "native-tls")]
+mod tunnel {
+    use std::{
+        io::{Error, ErrorKind},
+        net::SocketAddr,
+        sync::Arc,
+    };
+
+    use sha2::{Digest, Sha256};
+    use tokio::{
+        io::{AsyncRead, AsyncReadExt, AsyncWriteExt},
+        net::{TcpListener, TcpStream},
+    };
+
+    use super::TUNNEL_HEADER;
+
+    /// Longest request head read before the secret must have been found.
+    const MAX_HEAD: usize = 64 * 1024;
+
+    pub struct Tunnel {
+        pub host: String,
+        pub port: u16,
+        pub connector: tokio_native_tls::TlsConnector,
+        /// As [`normalize`] returns them.
+        pub fingerprints: Vec<String>,
+        pub secret: String,
+    }
+
+    impl Tunnel {
+        /// Accepts connections on a loopback port for as long as the runtime runs.
+        pub fn listen(self) -> Result<SocketAddr, Error> {
+            let runtime = tokio::runtime::Handle::try_current().map_err(Error::other)?;
+            let listener = std::net::TcpListener::bind(("127.0.0.1", 0))?;
+            listener.set_nonblocking(true)?;
+            let address = listener.local_addr()?;
+            let listener = {
+                let _entered = runtime.enter();
+                TcpListener::from_std(listener)?
+            };
+
+            let tunnel = Arc::new(self);
+            runtime.spawn(async move {
+                loop {
+                    let inbound = match listener.accept().await {
+                        Ok((inbound, _)) => inbound,
+                        Err(e) => {
+                            println!("Tunnel to {}:{} failed to accept a connection: {}", tunnel.host, tunnel.port, e);
+                            continue;
+                        }
+                    };
+                    let tunnel = tunnel.clone();
+                    tokio::spawn(async move {
+                        if let Err(e) = tunnel.forward(inbound).await {
+                            println!("Tunnel to {}:{} closed a connection: {}", tunnel.host, tunnel.port, e);
+                        }
+                    });
+                }
+            });
+            Ok(address)
+        }
+
+        async fn forward(&self, mut inbound: TcpStream) -> Result<(), Error> {
+            let head = read_head(&mut inbound).await?;
+            if !carries_secret(&head, &self.secret) {
+                return Err(Error::new(ErrorKind::PermissionDenied, "request without the tunnel secret"));
+            }
+
+            let outbound = TcpStream::connect((self.host.as_str(), self.port)).await?;
+            let mut outbound = self.connector.connect(&self.host, outbound).await.map_err(Error::other)?;
+            let der = outbound
+                .get_ref()
+                .peer_certificate()
+                .map_err(Error::other)?
+                .ok_or_else(|| Error::new(ErrorKind::InvalidData, "no certificate presented"))?
+                .to_der()
+                .map_err(Error::other)?;
+            let fingerprint = fingerprint(&der);
+            if !self.fingerprints.contains(&fingerprint) {
+                return Err(Error::new(
+                    ErrorKind::InvalidData,
+                    format!("certificate with unpinned fingerprint {}", fingerprint),
+                ));
+            }
+
+            outbound.write_all(&head).await?;
+            tokio::io::copy_bidirectional(&mut inbound, &mut outbound).await?;
+            Ok(())
+        }
+    }
+
+    /// A pinned fingerprint as [`fingerprint`] computes them: lowercase hex, without separators.
+    pub fn normalize(pinned: &str) -> String {
+        pinned.chars().filter(|c| *c != ':').collect::<String>().to_lowercase()
+    }
+
+    /// SHA-256 fingerprint of a DER-encoded certificate.
+    fn fingerprint(der: &[u8]) -> String {
+        hex::encode(Sha256::digest(der))
+    }
+
+    /// Reads at least the head of the first request on the connection.
+    async fn read_head(inbound: &mut (impl AsyncRead + Unpin)) -> Result<Vec<u8>, Error> {
+        let mut head = Vec::new();
+        let mut buffer = [0; 8192];
+        loop {
+            let read = inbound.read(&mut buffer).await?;
+            if read == 0 {
+                return Err(Error::new(ErrorKind::UnexpectedEof, "connection closed before a request"));
+            }
+            // Only the new bytes are scanned, from 3 bytes back in case the blank line straddles
+            // two reads.
+            let scanned = head.len().saturating_sub(3);
+            head.extend_from_slice(&buffer[..read]);
+            if head[scanned..].windows(4).any(|window| window == b"\r\n\r\n") {
+                return Ok(head);
+            }
+            if head.len() > MAX_HEAD {
+                return Err(Error::new(ErrorKind::InvalidData, "request head too long"));
+            }
+        }
+    }
+
+    fn carries_secret(head: &[u8], secret: &str) -> bool {
+        String::from_utf8_lossy(head)
+            .split("\r\n\r\n")
+            .next()
+            .unwrap_or_default()
+            .lines()
+            .skip(1)
+            .filter_map(|line| line.split_once(':'))
+            .any(|(name, value)| name.trim().eq_ignore_ascii_case(TUNNEL_HEADER) && value.trim() == secret)
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        const SECRET: &str = "0123456789abcdef";
+
+        fn request(headers: &str, body: &str) -> Vec<u8> {
+            format!("POST /posts/_search HTTP/1.1\r\nhost: 127.0.0.1\r\n{}\r\n{}", headers, body).into_bytes()
+        }
+
+        #[test]
+        fn the_secret_header_is_matched_case_insensitively_and_trimmed() {
+            for header in ["x-pinned-tunnel: 0123456789abcdef\r\n", "X-Pinned-Tunnel:0123456789abcdef\r\n", "X-PINNED-TUNNEL: \t0123456789abcdef \r\n"] {
+                assert!(carries_secret(&request(header, ""), SECRET), "{:?} should carry the secret", header);
+            }
+        }
+
+        #[test]
+        fn requests_without_the_secret_header_are_refused() {
+            assert!(!carries_secret(&request("", ""), SECRET));
+            assert!(!carries_secret(&request("x-pinned-tunnel: fedcba9876543210\r\n", ""), SECRET));
+            assert!(!carries_secret(&request("x-pinned-tunnel: 0123456789abcdef0\r\n", ""), SECRET));
+            assert!(!carries_secret(&request("x-pinned-tunnel-other: 0123456789abcdef\r\n", ""), SECRET));
+            assert!(!carries_secret(&request("x-forwarded: x-pinned-tunnel: 0123456789abcdef\r\n", ""), SECRET));
+        }
+
+        #[test]
+        fn a_secret_in_the_body_or_the_request_line_does_not_count() {
+            assert!(!carries_secret(&request("content-length: 34\r\n", "x-pinned-tunnel: 0123456789abcdef\r\n"), SECRET));
+            assert!(!carries_secret(b"GET /x-pinned-tunnel:0123456789abcdef HTTP/1.1\r\n\r\n", SECRET));
+        }
+
+        #[test]
+        fn pinned_fingerprints_are_normalized_to_computed_ones() {
+            // SHA-256 of "abc".
+            let pinned = "BA:78:16:BF:8F:01:CF:EA:41:41:40:DE:5D:AE:22:23:B0:03:61:A3:96:17:7A:9C:B4:10:FF:61:F2:00:15:AD";
+
+            assert_eq!(normalize(pinned), fingerprint(b"abc"));
+            assert_eq!(normalize(&normalize(pinned)), normalize(pinned));
+        }
+
+        #[tokio::test]
+        async fn heads_are_read_up_to_the_blank_line_even_across_reads() {
+            let head = request("x-pinned-tunnel: 0123456789abcdef\r\n", "");
+            let (mut client, mut server) = tokio::io::duplex(64);
+            let written = head.clone();
+            tokio::spawn(async move {
+                // One byte at a time, so the blank line spans several reads.
+                for byte in written {
+                    client.write_all(&[byte]).await.unwrap();
+                }
+            });
+
+            assert_eq!(read_head(&mut server).await.unwrap(), head);
+        }
+
+        #[tokio::test]
+        async fn heads_longer_than_the_limit_are_refused() {
+            let endless = format!("GET / HTTP/1.1\r\nx-padding: {}", "a".repeat(MAX_HEAD));
+
+            let error = read_head(&mut endless.as_bytes()).await.unwrap_err();
+
+            assert_eq!(error.kind(), ErrorKind::InvalidData);
+        }
+
+        #[tokio::test]
+        async fn connections_closed_before_a_head_are_refused() {
+            let error = read_head(&mut &b"GET / HTTP/1.1\r\n"[..]).await.unwrap_err();
+
+            assert_eq!(error.kind(), ErrorKind::UnexpectedEof);
+        }
+    }
+}
@@ -1,11 +1,126 @@
-// This file is intentionally left blank.
-// Define your data transfer objects (DTOs) here.
-// DTOs are used to transfer data between the interfaces layer and the application layer.
-// You can define structs or enums representing the data you want to transfer.
-
-// Example DTO:
-// pub struct UserDto {
-//     pub id: i32,
-//     pub name: String,
-//     pub email: String,
-// }
\ No newline at end of file
+use std::collections::BTreeMap;
+
+use rocket::serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::domain::{
+    access::Caller,
+    post::{Language, Level, PostType},
+    post_repository::PostResult,
+    search::{Facet, HighlightEncoder, Projection, SortOption},
+};
+
+/// Synonym rules in Solr format, e.g. `"js, javascript"` or `"ml => machine learning"`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SynonymsDto {
+    pub synonyms: Vec<String>,
+}
+
+/// Structured search filters. Repeated parameters (`level=beginner&level=advanced`)
+/// match any of the given values.
+#[derive(Debug, Default, FromForm)]
+pub struct SearchFiltersDto {
+    #[field(name = "type")]
+    pub post_type: Option<PostType>,
+    pub category: Vec<String>,
+    pub instructor: Option<String>,
+    pub level: Vec<Level>,
+    pub language: Vec<Language>,
+    pub price_min: Option<f64>,
+    pub price_max: Option<f64>,
+    pub rating_min: Option<f64>,
+    pub published: Option<bool>,
+}
+
+/// Highlighting options, given as `highlight.<option>` parameters.
+#[derive(Debug, Default, FromForm)]
+pub struct HighlightOptionsDto {
+    pub pre_tag: Option<String>,
+    pub post_tag: Option<String>,
+    pub fragment_size: Option<u32>,
+    pub number_of_fragments: Option<u32>,
+    pub require_field_match: Option<bool>,
+    pub encoder: Option<HighlightEncoder>,
+}
+
+/// A parsed search request.
+#[derive(Debug)]
+pub struct SearchRequestDto<'r> {
+    pub caller: Caller,
+    pub q: &'r str,
+    pub filters: SearchFiltersDto,
+    pub facets: Vec<Facet>,
+    pub sort: SortOption,
+    pub projection: Projection,
+    pub highlight: HighlightOptionsDto,
+    pub explain: bool,
+    pub page: Option<u32>,
+    pub per_page: Option<u32>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TermBucketDto {
+    pub key: String,
+    pub count: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RangeBucketDto {
+    pub key: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub from: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub to: Option<f64>,
+    pub count: u64,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum FacetDto {
+    Terms { buckets: Vec<TermBucketDto> },
+    Range { buckets: Vec<RangeBucketDto> },
+}
+
+impl FacetDto {
+    /// Builds the facet from the aggregation produced by `Facet::aggregation`.
+    pub fn from_aggregation(facet: &Facet, aggregation: &Value) -> Self {
+        let buckets = aggregation["values"]["buckets"]
+            .as_array()
+            .cloned()
+            .unwrap_or_default();
+        let count = |bucket: &Value| bucket["doc_count"].as_u64().unwrap_or(0);
+        let key = |bucket: &Value| match &bucket["key"] {
+            Value::String(key) => key.clone(),
+            key => key.to_string(),
+        };
+
+        match facet {
+            Facet::Price => FacetDto::Range {
+                buckets: buckets
+                    .iter()
+                    .map(|bucket| RangeBucketDto {
+                        key: key(bucket),
+                        from: bucket["from"].as_f64(),
+                        to: bucket["to"].as_f64(),
+                        count: count(bucket),
+                    })
+                    .collect(),
+            },
+            _ => FacetDto::Terms {
+                buckets: buckets
+                    .iter()
+                    .map(|bucket| TermBucketDto {
+                        key: key(bucket),
+                        count: count(bucket),
+                    })
+                    .collect(),
+            },
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct SearchResponseDto {
+    pub hits: Vec<PostResult>,
+    pub facets: BTreeMap<String, FacetDto>,
+}
@@ -0,0 +1,43 @@
+use std::{sync::Arc, time::Instant};
+
+use rocket::{
+    fairing::{Fairing, Info, Kind},
+    Data, Request, Response,
+};
+
+use crate::infrastructure::metrics::Metrics;
+
+/// Counts requests and times them per route.
+pub struct RequestMetrics(pub Arc<Metrics>);
+
+/// When the request was received.
+struct RequestStart(Instant);
+
+#[rocket::async_trait]
+impl Fairing for RequestMetrics {
+    fn info(&self) -> Info {
+        Info {
+            name: "Request metrics",
+            kind: Kind::Request | Kind::Response,
+        }
+    }
+
+    async fn on_request(&self, req: &mut Request<'_>, _: &mut Data<'_>) {
+        req.local_cache(|| RequestStart(Instant::now()));
+    }
+
+    async fn on_response<'r>(&self, req: &'r Request<'_>, res: &mut Response<'r>) {
+        let elapsed = req.local_cache(|| RequestStart(Instant::now())).0.elapsed();
+        // Routes are labeled by their template, so ids in paths do not multiply the series.
+        let route = req.route().map(|route| route.uri.path().to_string()).unwrap_or_else(|| "unmatched".into());
+        let method = req.method().as_str();
+        self.0
+            .http_requests
+            .with_label_values(&[method, &route, &res.status().code.to_string()])
+            .inc();
+        self.0
+            .http_request_duration
+            .with_label_values(&[method, &route])
+            .observe(elapsed.as_secs_f64());
+    }
+}
@@ -0,0 +1,240 @@
+use rocket::{
+    http::Status,
+    request::{FromRequest, Outcome, Request},
+};
+
+use crate::{
+    domain::access::{Access, Caller, Scope},
+    infrastructure::{auth::Authenticator, config::TenantSettings},
+};
+
+/// Why a request guard rejected the request, for the 401/403 catchers to report.
+pub struct AuthError(pub String);
+
+fn reject<T>(req: &Request<'_>, status: Status, message: String) -> Outcome<T, String> {
+    req.local_cache(|| AuthError(message.clone()));
+    Outcome::Error((status, message))
+}
+
+/// Reads the caller from an `Authorization: Bearer <token>` header. A request without the
+/// header is anonymous; a request with an invalid token is rejected.
+///
+/// Service tokens serve every tenant, so services name the one they act for with `X-Tenant-Id`.
+/// Everyone else gets the tenant of their token's `tenant` claim, or the default tenant, and is
+/// refused when the header names another one.
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Caller {
+    type Error = String;
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let mut caller = match req.headers().get_one("Authorization") {
+            None => Caller::anonymous(),
+            Some(header) => {
+                let Some(token) = header.strip_prefix("Bearer ") else {
+                    return reject(req, Status::Unauthorized, "Expected a bearer token".into());
+                };
+                let Some(authenticator) = req.rocket().state::<Authenticator>() else {
+                    return reject(req, Status::InternalServerError, "Authenticator is not managed".into());
+                };
+                match authenticator.authenticate(token.trim()) {
+                    Ok(caller) => caller,
+                    Err(e) => return reject(req, Status::Unauthorized, e),
+                }
+            }
+        };
+
+        if let Some(tenant) = req.headers().get_one("X-Tenant-Id") {
+            if caller.service {
+                caller.tenant_id = Some(tenant.to_string());
+            } else if tenant != caller.tenant() {
+                return reject(req, Status::Forbidden, format!("Caller is bound to tenant {}", caller.tenant()));
+            }
+        }
+        if !Access::is_valid_tenant(caller.tenant()) {
+            return reject(req, Status::BadRequest, format!("Invalid tenant: {}", caller.tenant()));
+        }
+        if req.rocket().state::<TenantSettings>().is_some_and(|tenants| !tenants.allows(caller.tenant())) {
+            return reject(req, Status::Forbidden, format!("Unknown tenant: {}", caller.tenant()));
+        }
+
+        Outcome::Success(caller)
+    }
+}
+
+/// The authenticated caller, when it has any of `scopes`.
+async fn caller_with_scope(req: &Request<'_>, scopes: &[Scope]) -> Outcome<Caller, String> {
+    let caller = match req.guard::<Caller>().await {
+        Outcome::Success(caller) => caller,
+        Outcome::Error(e) => return Outcome::Error(e),
+        Outcome::Forward(status) => return Outcome::Forward(status),
+    };
+
+    if caller.is_anonymous() {
+        return reject(req, Status::Unauthorized, "Authentication required".into());
+    }
+    if !scopes.iter().any(|scope| caller.has_scope(*scope)) {
+        let names: Vec<String> = scopes.iter().map(|scope| format!("{:?}", scope)).collect();
+        return reject(req, Status::Forbidden, format!("Missing scope: {}", names.join(" or ")));
+    }
+    Outcome::Success(caller)
+}
+
+/// A caller allowed to index, update and delete posts.
+pub struct Indexer(pub Caller);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Indexer {
+    type Error = String;
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        caller_with_scope(req, &[Scope::Index]).await.map(Indexer)
+    }
+}
+
+/// A caller allowed to administer the index. The index, its synonyms and its migrations are
+/// shared by every tenant, so only the admin service token qualifies; tenant-bound tokens with
+/// the admin scope do not.
+pub struct Administrator;
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Administrator {
+    type Error = String;
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let caller = match caller_with_scope(req, &[Scope::Admin]).await {
+            Outcome::Success(caller) => caller,
+            Outcome::Error(e) => return Outcome::Error(e),
+            Outcome::Forward(status) => return Outcome::Forward(status),
+        };
+        if !caller.service {
+            return reject(req, Status::Forbidden, "The index is shared by every tenant, administer it with the admin service token".into());
+        }
+        Outcome::Success(Administrator)
+    }
+}
+
+/// A caller allowed to follow the tasks started by updates: indexers, who get their ids in
+/// update responses, and administrators.
+pub struct TaskReader(pub Caller);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for TaskReader {
+    type Error = String;
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        caller_with_scope(req, &[Scope::Index, Scope::Admin]).await.map(TaskReader)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use jsonwebtoken::{encode, EncodingKey, Header};
+    use rocket::{http::Header as HttpHeader, local::asynchronous::Client, routes};
+    use serde_json::json;
+
+    use super::*;
+    use crate::infrastructure::config::{AuthSettings, Secret};
+
+    const JWT_SECRET: &str = "jwt-secret";
+    const ADMIN_TOKEN: &str = "admin-token";
+
+    #[rocket::get("/admin")]
+    fn admin(_admin: Administrator) -> &'static str {
+        "ok"
+    }
+
+    #[rocket::get("/posts")]
+    fn posts(indexer: Indexer) -> String {
+        indexer.0.tenant().to_string()
+    }
+
+    async fn client() -> Client {
+        let settings = AuthSettings {
+            jwt_secret: Some(Secret::new(JWT_SECRET)),
+            admin_token: Some(Secret::new(ADMIN_TOKEN)),
+            ..AuthSettings::default()
+        };
+        let rocket = rocket::build()
+            .manage(Authenticator::new(&settings).unwrap())
+            .mount("/", routes![admin, posts]);
+        Client::tracked(rocket).await.unwrap()
+    }
+
+    fn jwt(claims: serde_json::Value) -> String {
+        encode(&Header::default(), &claims, &EncodingKey::from_secret(JWT_SECRET.as_bytes())).unwrap()
+    }
+
+    fn tenant_token(tenant: &str, scope: &str) -> String {
+        jwt(json!({
+            "sub": uuid::Uuid::new_v4(),
+            "tenant": tenant,
+            "scope": scope,
+            "exp": 4_102_444_800u64
+        }))
+    }
+
+    async fn get(client: &Client, uri: &str, token: &str) -> Status {
+        client
+            .get(uri.to_string())
+            .header(HttpHeader::new("Authorization", format!("Bearer {}", token)))
+            .dispatch()
+            .await
+            .status()
+    }
+
+    #[rocket::async_test]
+    async fn tenant_tokens_with_the_admin_scope_cannot_administer_the_shared_index() {
+        let client = client().await;
+        let token = tenant_token("acme", "search:admin");
+
+        assert_eq!(get(&client, "/admin", &token).await, Status::Forbidden);
+    }
+
+    #[rocket::async_test]
+    async fn the_admin_service_token_administers_the_index() {
+        let client = client().await;
+
+        assert_eq!(get(&client, "/admin", ADMIN_TOKEN).await, Status::Ok);
+    }
+
+    #[rocket::async_test]
+    async fn writes_require_a_token() {
+        let client = client().await;
+
+        assert_eq!(client.get("/posts").dispatch().await.status(), Status::Unauthorized);
+        assert_eq!(get(&client, "/posts", "not-a-token").await, Status::Unauthorized);
+        let basic = client.get("/posts").header(HttpHeader::new("Authorization", "Basic YWRtaW46YWRtaW4=")).dispatch().await;
+        assert_eq!(basic.status(), Status::Unauthorized);
+    }
+
+    #[rocket::async_test]
+    async fn writes_require_the_index_scope() {
+        let client = client().await;
+
+        assert_eq!(get(&client, "/posts", &tenant_token("acme", "openid")).await, Status::Forbidden);
+        assert_eq!(get(&client, "/posts", &tenant_token("acme", "search:admin")).await, Status::Forbidden);
+        assert_eq!(get(&client, "/posts", &tenant_token("acme", "search:index")).await, Status::Ok);
+    }
+
+    #[rocket::async_test]
+    async fn tenant_tokens_cannot_act_for_another_tenant() {
+        let client = client().await;
+        let token = tenant_token("acme", "search:index");
+
+        let response = client
+            .get("/posts")
+            .header(HttpHeader::new("Authorization", format!("Bearer {}", token)))
+            .header(HttpHeader::new("X-Tenant-Id", "globex"))
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::Forbidden);
+
+        let response = client
+            .get("/posts")
+            .header(HttpHeader::new("Authorization", format!("Bearer {}", token)))
+            .header(HttpHeader::new("X-Tenant-Id", "acme"))
+            .dispatch()
+            .await;
+        assert_eq!(response.into_string().await.as_deref(), Some("acme"));
+    }
+}
@@ -1,2 +1,5 @@
 // This file is intentionally left blank.
+pub mod dtos;
+pub mod fairings;
+pub mod guards;
 pub mod routes;
\ No newline at end of file
@@ -1,7 +1,9 @@
-use rocket::{response::status::Custom, serde::json::Json, Route, State};
+use std::sync::Arc;
+
+use rocket::{http::{ContentType, Status}, response::status::Custom, serde::json::{json, Json}, Route, State};
 use uuid::Uuid;
 
-use crate::{domain::post::Post, App};
+use crate::{domain::{access::Caller, health::HealthStatus, post::Post, search::{Facet, Projection, SortOption}}, interfaces::{dtos::{HighlightOptionsDto, SearchFiltersDto, SearchRequestDto, SynonymsDto}, guards::{Administrator, Indexer, TaskReader}}, infrastructure::metrics::Metrics, App};
 
 pub fn routes() -> Vec<Route> {
     // Define your routes here
@@ -17,6 +19,28 @@ pub fn routes() -> Vec<Route> {
     ]
 }
 
+pub fn metrics_routes() -> Vec<Route> {
+    routes![
+        metrics
+    ]
+}
+
+pub fn health_routes() -> Vec<Route> {
+    routes![
+        live,
+        ready
+    ]
+}
+
+pub fn admin_routes() -> Vec<Route> {
+    routes![
+        get_synonyms,
+        update_synonyms,
+        get_task,
+        reindex
+    ]
+}
+
 #[get("/users")]
 fn get_users() -> &'static str {
     // Implement the logic to get all users
@@ -41,27 +65,84 @@ fn delete_user(id: u32) -> String {
     format!("Delete user with ID: {}", id)
 }
 
-#[get("/?<q>")]
-async fn search_post(q: &str, app: &State<App<'_>>) -> Result<Custom<sea_orm::prelude::Json>, Custom<sea_orm::prelude::Json>> {
+#[get("/?<q>&<facets>&<sort>&<fields>&<highlight>&<explain>&<page>&<per_page>&<filters..>")]
+#[allow(clippy::too_many_arguments)]
+async fn search_post(caller: Caller, q: &str, facets: Option<&str>, sort: Option<SortOption>, fields: Option<&str>, highlight: HighlightOptionsDto, explain: Option<bool>, page: Option<u32>, per_page: Option<u32>, filters: SearchFiltersDto, app: &State<App>) -> Result<Custom<sea_orm::prelude::Json>, Custom<sea_orm::prelude::Json>> {
     // Implement the logic to search a post
-    app.search_post_use_case.execute(q).await
+    let facets = match Facet::parse_list(facets.unwrap_or_default()) {
+        Ok(facets) => facets,
+        Err(e) => return Err(Custom(Status::BadRequest, json!({"success": false, "message": e})))
+    };
+    let projection = match Projection::parse(fields) {
+        Ok(projection) => projection,
+        Err(e) => return Err(Custom(Status::BadRequest, json!({"success": false, "message": e})))
+    };
+    app.search_post_use_case.execute(&SearchRequestDto {
+        caller,
+        q,
+        filters,
+        facets,
+        sort: sort.unwrap_or_default(),
+        projection,
+        highlight,
+        explain: explain.unwrap_or(false),
+        page,
+        per_page,
+    }).await
 }
 
 #[post("/", data="<post>")]
-async fn index_post(post: Json<Post>, app: &State<App<'_>>) -> Result<Custom<sea_orm::prelude::Json>, Custom<sea_orm::prelude::Json>> {
-    app.index_post_use_case.execute(post).await
+async fn index_post(indexer: Indexer, post: Json<Post>, app: &State<App>) -> Result<Custom<sea_orm::prelude::Json>, Custom<sea_orm::prelude::Json>> {
+    app.index_post_use_case.execute(post, indexer.0.tenant()).await
 }
 
 #[put("/<post_id>", data="<post>")]
-async fn update_post(post_id: &str, post: Json<Post>, app: &State<App<'_>>) -> Result<Custom<sea_orm::prelude::Json>, Custom<sea_orm::prelude::Json>> {
+async fn update_post(indexer: Indexer, post_id: &str, post: Json<Post>, app: &State<App>) -> Result<Custom<sea_orm::prelude::Json>, Custom<sea_orm::prelude::Json>> {
     // Implement the logic to update a post
     let id = Uuid::parse_str(post_id).unwrap();
-    app.update_post_use_case.execute(&id, post).await
+    app.update_post_use_case.execute(&id, post, indexer.0.tenant()).await
 }
 
 #[delete("/<post_id>")]
-async fn delete_post(post_id: &str, app: &State<App<'_>>) -> Result<Custom<sea_orm::prelude::Json>, Custom<sea_orm::prelude::Json>> {
+async fn delete_post(indexer: Indexer, post_id: &str, app: &State<App>) -> Result<Custom<sea_orm::prelude::Json>, Custom<sea_orm::prelude::Json>> {
     // Implement the logic to delete a post
     let id = Uuid::parse_str(post_id).unwrap();
-    app.delete_post_use_case.execute(&id).await
-}
\ No newline at end of file
+    app.delete_post_use_case.execute(&id, indexer.0.tenant()).await
+}
+
+#[get("/synonyms")]
+async fn get_synonyms(_admin: Administrator, app: &State<App>) -> Result<Json<SynonymsDto>, Custom<sea_orm::prelude::Json>> {
+    app.get_synonyms_use_case.execute().await.map(Json)
+}
+
+#[put("/synonyms", data="<synonyms>")]
+async fn update_synonyms(_admin: Administrator, synonyms: Json<SynonymsDto>, app: &State<App>) -> Result<Custom<sea_orm::prelude::Json>, Custom<sea_orm::prelude::Json>> {
+    app.update_synonyms_use_case.execute(synonyms).await
+}
+
+#[get("/tasks/<task_id>")]
+async fn get_task(reader: TaskReader, task_id: &str, app: &State<App>) -> Result<Custom<sea_orm::prelude::Json>, Custom<sea_orm::prelude::Json>> {
+    app.get_task_use_case.execute(task_id, &reader.0).await
+}
+
+#[post("/reindex")]
+async fn reindex(_admin: Administrator, app: &State<App>) -> Result<Custom<sea_orm::prelude::Json>, Custom<sea_orm::prelude::Json>> {
+    app.reindex_use_case.execute().await
+}
+
+#[get("/metrics")]
+fn metrics(metrics: &State<Arc<Metrics>>) -> (ContentType, String) {
+    let (content_type, body) = metrics.render();
+    (ContentType::parse_flexible(&content_type).unwrap_or(ContentType::Plain), body)
+}
+
+/// Up as long as the service handles requests; dependencies are only checked by `ready`.
+#[get("/live")]
+fn live() -> sea_orm::prelude::Json {
+    json!({"status": HealthStatus::Up})
+}
+
+#[get("/ready")]
+async fn ready(app: &State<App>) -> Custom<sea_orm::prelude::Json> {
+    app.check_readiness_use_case.execute().await
+}
@@ -1,5 +1,7 @@
-use application::use_cases::{delete_post_use_case::DeletePostUseCase, index_post_use_case::IndexPostUseCase, search_post_use_case::SearchPostUseCase, update_post_use_case::UpdatePostUseCase};
+use application::use_cases::{check_readiness_use_case::CheckReadinessUseCase, delete_post_use_case::DeletePostUseCase, get_synonyms_use_case::GetSynonymsUseCase, get_task_use_case::GetTaskUseCase, index_post_use_case::IndexPostUseCase, reindex_use_case::ReindexUseCase, search_post_use_case::SearchPostUseCase, update_post_use_case::UpdatePostUseCase, update_synonyms_use_case::UpdateSynonymsUseCase};
 use domain::post_repository::PostRepositoryImpl;
+use infrastructure::{auth::Authenticator, config::Settings, metrics::Metrics};
+use interfaces::{fairings::RequestMetrics, guards::AuthError};
 use rocket::{http::Status, Request};
 use serde_json::{json, Value};
 use std::sync::Arc;
@@ -14,28 +16,54 @@ mod application;
 mod infrastructure;
 mod interfaces;
 
-pub struct App<'a> {
-    pub post_repository: Arc<PostRepositoryImpl<'a>>,
-    pub search_post_use_case: SearchPostUseCase<'a>,
-    pub index_post_use_case: IndexPostUseCase<'a>,
-    pub update_post_use_case: UpdatePostUseCase<'a>,
-    pub delete_post_use_case: DeletePostUseCase<'a>,
+pub struct App {
+    pub post_repository: Arc<PostRepositoryImpl>,
+    pub search_post_use_case: SearchPostUseCase,
+    pub index_post_use_case: IndexPostUseCase,
+    pub update_post_use_case: UpdatePostUseCase,
+    pub delete_post_use_case: DeletePostUseCase,
+    pub get_synonyms_use_case: GetSynonymsUseCase,
+    pub update_synonyms_use_case: UpdateSynonymsUseCase,
+    pub get_task_use_case: GetTaskUseCase,
+    pub reindex_use_case: ReindexUseCase,
+    pub check_readiness_use_case: CheckReadinessUseCase,
 }
 
-impl <'a> App <'a> {
-    pub fn new() -> Self {
-        let post_repository = Arc::new(PostRepositoryImpl::new());
+impl App {
+    pub fn new(settings: &Settings, metrics: Arc<Metrics>) -> Self {
+        let post_repository = Arc::new(PostRepositoryImpl::new(settings, metrics));
         App {
             post_repository: post_repository.clone(),
-            search_post_use_case: SearchPostUseCase::new(post_repository.clone()),
+            search_post_use_case: SearchPostUseCase::new(post_repository.clone(), settings.search.clone()),
             index_post_use_case: IndexPostUseCase::new(post_repository.clone()),
             update_post_use_case: UpdatePostUseCase::new(post_repository.clone()),
             delete_post_use_case: DeletePostUseCase::new(post_repository.clone()),
+            get_synonyms_use_case: GetSynonymsUseCase::new(post_repository.clone()),
+            update_synonyms_use_case: UpdateSynonymsUseCase::new(post_repository.clone()),
+            get_task_use_case: GetTaskUseCase::new(post_repository.clone()),
+            reindex_use_case: ReindexUseCase::new(post_repository.clone()),
+            check_readiness_use_case: CheckReadinessUseCase::new(post_repository.clone()),
         }
     }
 
 }
 
+#[catch(401)]
+fn unauthorized(req: &Request) -> Value {
+    json!({
+        "success": false,
+        "message": req.local_cache(|| AuthError("Authentication required".into())).0
+    })
+}
+
+#[catch(403)]
+fn forbidden(req: &Request) -> Value {
+    json!({
+        "success": false,
+        "message": req.local_cache(|| AuthError("Forbidden".into())).0
+    })
+}
+
 #[catch(404)]
 fn not_found(req: &Request) -> Value {
     json!({
@@ -63,11 +91,21 @@ fn default(status: Status, req: &Request) -> Value {
 
 #[rocket::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>>{
-    let app = App::new();
-    rocket::build()
+    let figment = Settings::figment();
+    let settings = Settings::from_figment(&figment)?;
+    let metrics = Arc::new(Metrics::new());
+    let app = App::new(&settings, metrics.clone());
+    rocket::custom(figment)
+    .attach(RequestMetrics(metrics.clone()))
     .manage(app)
-    .register("/", catchers![internal_error, not_found, default])
+    .manage(metrics)
+    .manage(Authenticator::new(&settings.auth)?)
+    .manage(settings.tenants.clone())
+    .register("/", catchers![unauthorized, forbidden, internal_error, not_found, default])
     .mount("/posts", interfaces::routes::routes())
+    .mount("/admin", interfaces::routes::admin_routes())
+    .mount("/health", interfaces::routes::health_routes())
+    .mount("/", interfaces::routes::metrics_routes())
     .launch()
     .await?;
 